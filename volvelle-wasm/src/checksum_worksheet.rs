@@ -16,9 +16,15 @@
 
 use crate::error::Error;
 use crate::fe::{self, Checksum, Fe};
-use std::collections::VecDeque;
+use serde::Serialize;
+use std::collections::{HashSet, VecDeque};
+use std::iter;
 use wasm_bindgen::prelude::*;
 
+/// Characters which are easily confused with another character in the alphabet
+/// when handwritten: 0/D/Q, 2/Z, 5/S, 6/G and U/V
+const CONFUSABLE_CHARS: &str = "0DQ2Z5S6GUV";
+
 /// How to render a given cell
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum CellType {
@@ -30,33 +36,141 @@ pub enum CellType {
     Sum,
     /// Global `SECRETSHARE32` residue, fixed in "create" mode but computed in "verify" mode
     GlobalResidue,
+    /// Fixed zero used to pad the share data to an even length; never editable
+    Padding,
 }
 
 impl CellType {
-    fn text(&self, is_checksum: bool) -> &'static str {
+    fn kind(&self, is_checksum: bool) -> DomCellKind {
         match *self {
             CellType::ShareData => {
                 if is_checksum {
-                    "share_data_checksum"
+                    DomCellKind::ShareDataChecksum
                 } else {
-                    "share_data"
+                    DomCellKind::ShareData
                 }
             }
-            CellType::Residue => "residue",
+            CellType::Residue => DomCellKind::Residue,
             CellType::Sum => {
                 if is_checksum {
-                    "sum_checksum"
+                    DomCellKind::SumChecksum
                 } else {
-                    "sum"
+                    DomCellKind::Sum
                 }
             }
-            CellType::GlobalResidue => "global_residue",
+            CellType::GlobalResidue => DomCellKind::GlobalResidue,
+            CellType::Padding => DomCellKind::Padding,
+        }
+    }
+}
+
+/// The kind of a rendered cell, which determines how the frontend styles it
+///
+/// This includes the render-only cells (the HRP and the `+`/`=` symbols) as well
+/// as every `CellType`. It serializes as its `as_str` string.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DomCellKind {
+    /// A character of the HRP, or the `1` separator
+    FixedHrp,
+    /// A `+` or `=` between rows
+    Symbol,
+    ShareData,
+    /// Share data which is back-computed from the global residue
+    ShareDataChecksum,
+    Residue,
+    Sum,
+    /// A sum which is back-computed from the global residue
+    SumChecksum,
+    GlobalResidue,
+    Padding,
+}
+
+impl DomCellKind {
+    /// Every kind of cell
+    pub const ALL: [DomCellKind; 9] = [
+        DomCellKind::FixedHrp,
+        DomCellKind::Symbol,
+        DomCellKind::ShareData,
+        DomCellKind::ShareDataChecksum,
+        DomCellKind::Residue,
+        DomCellKind::Sum,
+        DomCellKind::SumChecksum,
+        DomCellKind::GlobalResidue,
+        DomCellKind::Padding,
+    ];
+
+    /// The string passed to the frontend for this kind of cell
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            DomCellKind::FixedHrp => "fixed_hrp",
+            DomCellKind::Symbol => "symbol",
+            DomCellKind::ShareData => "share_data",
+            DomCellKind::ShareDataChecksum => "share_data_checksum",
+            DomCellKind::Residue => "residue",
+            DomCellKind::Sum => "sum",
+            DomCellKind::SumChecksum => "sum_checksum",
+            DomCellKind::GlobalResidue => "global_residue",
+            DomCellKind::Padding => "padding",
         }
     }
 }
 
+/// What the worksheet is being used for, which determines which cells are computed
+#[wasm_bindgen]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CreateMode {
+    /// Creating a new share: the global residue is fixed and the checksum
+    /// characters are computed backward from it
+    Create = 0,
+    /// Verifying an existing share: every share character is entered and the
+    /// global residue is computed
+    Verify = 1,
+    /// Working through a share entirely by hand: nothing is filled in
+    /// automatically, not even the HRP residue or the target residue, and every
+    /// residue and sum the user enters is checked against its expected value
+    Blank = 2,
+}
+
+/// How the worksheet's cells are arranged on the page
+#[wasm_bindgen]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Layout {
+    /// Rows of the worksheet run left to right, shifting right as they go down
+    Diagonal = 0,
+    /// Rows of the worksheet run top to bottom, which is narrower on small screens
+    Transposed = 1,
+}
+
+/// State of the global residue row, for display
+#[wasm_bindgen]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum GlobalResidueStatus {
+    /// Not enough data has been entered to compute the whole residue
+    Incomplete = 0,
+    /// The residue matches the checksum's target
+    Valid = 1,
+    /// The residue is fully computed but does not match the target
+    Invalid = 2,
+}
+
+/// Why a worksheet's checksum does not validate
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FailureKind {
+    /// The checksum is valid
+    NoFailure,
+    /// Not all of the share data has been entered
+    Incomplete,
+    /// The data is a valid string under a different checksum
+    WrongChecksumType(Checksum),
+    /// The data contains errors
+    DataError,
+}
+
 /// A single cell of the checksum worksheet
-#[derive(Clone, PartialEq, Eq, Debug)]
+///
+/// Cells compare equal if they hold the same value, regardless of when it was input.
+#[derive(Clone, Debug)]
 pub struct Cell {
     ty: CellType,
     /// Whether this cell is far enough to the right of the sheet to be a
@@ -66,8 +180,28 @@ pub struct Cell {
     dom_id: String,
     /// The value in this cell, unless it is blank
     val: Option<Fe>,
+    /// The worksheet revision at which this cell was last input by the user, or 0
+    seq: u64,
+    /// Whether this computed cell has been manually set, and should not be recomputed
+    overridden: bool,
+}
+
+impl PartialEq for Cell {
+    fn eq(&self, other: &Cell) -> bool {
+        self.ty == other.ty
+            && self.is_checksum == other.is_checksum
+            && self.dom_id == other.dom_id
+            && self.val == other.val
+            && self.overridden == other.overridden
+    }
 }
 
+impl Eq for Cell {}
+
+/// A share data position where two worksheets differ: the position (after the HRP
+/// and separator), and the value in each worksheet
+pub type ShareDataDiff = (usize, Option<char>, Option<char>);
+
 /// A row in the worksheet
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Row {
@@ -77,16 +211,18 @@ pub struct Row {
 
 impl Checksum {
     /// Length of the generator polynomial
-    fn len(&self) -> usize {
+    pub(crate) fn len(&self) -> usize {
         match *self {
             Checksum::Codex32 => 13,
             Checksum::Bech32 => 6,
+            Checksum::Codex32Long => 15,
         }
     }
 }
 
 /// Action to perform on the DOM
 #[wasm_bindgen]
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Action {
     /// What to do
     ty: &'static str,
@@ -95,6 +231,23 @@ pub struct Action {
     /// Data for the action (e.g. to set a cell to a particular value)
     pub value: Option<char>,
 }
+#[cfg(test)]
+impl Action {
+    /// Constructs an action, for comparison in tests
+    fn new(ty: &'static str, id: &str, value: Option<char>) -> Action {
+        Action {
+            ty,
+            id: id.into(),
+            value,
+        }
+    }
+
+    /// Flattens an action into a tuple, for comparison in tests
+    pub(crate) fn as_tuple(&self) -> (&'static str, &str, Option<char>) {
+        (self.ty, &self.id, self.value)
+    }
+}
+
 #[wasm_bindgen]
 impl Action {
     // Need to manually implement accessors for now with wasm_bindgen
@@ -106,13 +259,21 @@ impl Action {
     pub fn id(&self) -> String {
         self.id.clone()
     }
+    /// The value as a two-digit decimal string, for numeric rendering
+    #[wasm_bindgen(getter)]
+    pub fn value_num(&self) -> Option<String> {
+        self.value
+            .and_then(|ch| Fe::try_from(ch).ok())
+            .map(|fe| fe.to_numeric_string())
+    }
 }
 
 /// Cell to construct in the DOM
 #[wasm_bindgen]
 pub struct DomCell {
-    ty: &'static str,
+    ty: DomCellKind,
     dom_id: String,
+    text: String,
     pub val: Option<char>,
     pub x: usize,
     pub y: usize,
@@ -123,62 +284,327 @@ impl DomCell {
     // Need to manually implement accessors for now with wasm_bindgen
     #[wasm_bindgen(getter)]
     pub fn ty(&self) -> String {
-        self.ty.into()
+        self.ty.as_str().into()
     }
     #[wasm_bindgen(getter)]
     pub fn dom_id(&self) -> String {
         self.dom_id.clone()
     }
+    #[wasm_bindgen(getter)]
+    pub fn text(&self) -> String {
+        self.text.clone()
+    }
+}
+
+/// Compact status of a share, for display in the share list
+#[wasm_bindgen]
+#[derive(Clone, PartialEq, Debug)]
+pub struct ShareSummary {
+    header: String,
+    /// Fraction of the share data which has been filled in
+    pub completion: f64,
+    /// Whether the share is complete and has a valid checksum
+    pub valid: bool,
+    /// The share index, if it has been filled in
+    pub index: Option<char>,
+    /// Whether the share is being created or verified
+    pub mode: CreateMode,
+}
+
+#[wasm_bindgen]
+impl ShareSummary {
+    // Need to manually implement accessors for now with wasm_bindgen
+    #[wasm_bindgen(getter)]
+    pub fn header(&self) -> String {
+        self.header.clone()
+    }
+}
+
+/// Cell to construct in the DOM, as plain data for non-wasm consumers
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
+pub struct DomCellData {
+    pub ty: DomCellKind,
+    pub dom_id: String,
+    /// The text to display in the cell, which is `val` unless rendering numerically
+    pub text: String,
+    pub val: Option<char>,
+    pub x: usize,
+    pub y: usize,
+    /// Whether the user is expected to type into this cell
+    pub editable: bool,
+}
+
+impl From<DomCellData> for DomCell {
+    fn from(data: DomCellData) -> Self {
+        DomCell {
+            ty: data.ty,
+            dom_id: data.dom_id,
+            text: data.text,
+            val: data.val,
+            x: data.x,
+            y: data.y,
+        }
+    }
 }
 
 /// The entire checksum worksheet
-#[derive(Clone, PartialEq, Eq, Debug)]
+///
+/// As with cells, worksheets compare equal if they hold the same values, regardless
+/// of their revisions.
+#[derive(Clone, Debug)]
 pub struct Worksheet {
     hrp: String,
+    /// Size of the worksheet layout, including the padding cell if any
     size: usize,
     rows: Vec<Row>,
     checksum: Checksum,
     idx: usize,
+    /// Whether the first data cell is a fixed zero padding cell
+    padded: bool,
+    mode: CreateMode,
+    /// Whether lowercase input is uppercased with a `flash_set`, rather than a plain `set`
+    flash_case: bool,
+    /// Whether emitted actions are being appended to `action_history`
+    recording_actions: bool,
+    /// Every action emitted while recording, for testing the frontend
+    action_history: Vec<Action>,
+    /// The number of the latest user input to the worksheet, or 0 if there was none
+    revision: u64,
+}
+
+impl PartialEq for Worksheet {
+    fn eq(&self, other: &Worksheet) -> bool {
+        self.hrp == other.hrp
+            && self.size == other.size
+            && self.rows == other.rows
+            && self.checksum == other.checksum
+            && self.idx == other.idx
+            && self.padded == other.padded
+            && self.mode == other.mode
+            && self.flash_case == other.flash_case
+            && self.recording_actions == other.recording_actions
+            && self.action_history == other.action_history
+    }
 }
 
+impl Eq for Worksheet {}
+
 impl Worksheet {
     /// Constructs a new blank worksheet
     pub fn new(hrp: &str, size: usize, checksum: Checksum, idx: usize) -> Result<Worksheet, Error> {
+        Worksheet::new_inner(hrp, size, checksum, idx, false)
+    }
+
+    /// Constructs a new blank worksheet, padding the data to an even length if needed
+    ///
+    /// If the data length is odd, a fixed `Q` padding cell is inserted at the start
+    /// of the data. It does not affect the checksum and is stripped on export.
+    pub fn new_padded(
+        hrp: &str,
+        size: usize,
+        checksum: Checksum,
+        idx: usize,
+    ) -> Result<Worksheet, Error> {
+        Worksheet::new_inner(hrp, size, checksum, idx, true)
+    }
+
+    fn new_inner(
+        hrp: &str,
+        size: usize,
+        checksum: Checksum,
+        idx: usize,
+        allow_padding: bool,
+    ) -> Result<Worksheet, Error> {
         let mut ret = Worksheet {
             hrp: hrp.to_string().to_ascii_uppercase(),
             size,
             rows: vec![],
             checksum,
             idx,
+            padded: false,
+            mode: CreateMode::Create,
+            flash_case: true,
+            recording_actions: false,
+            action_history: vec![],
+            revision: 0,
         };
 
-        if size < ret.hrp.len() + checksum.len() {
+        if hrp.is_empty() {
+            return Err(Error::EmptyHrp);
+        }
+        // HRP, separator and checksum
+        let minimum = ret.hrp.len() + 1 + checksum.len();
+        if size < minimum {
             return Err(Error::TooShort {
-                minimum: ret.hrp.len() + checksum.len(),
+                minimum,
                 actual: size,
             });
         }
-        let data_len = size - ret.hrp.len() - checksum.len() - 1;
+        checksum.check_size(ret.hrp.len(), size)?;
+        let mut data_len = size - minimum;
         if data_len % 2 == 1 {
-            return Err(Error::OddLength { data_len });
+            if !allow_padding {
+                return Err(Error::OddLength { data_len });
+            }
+            ret.padded = true;
+            ret.size += 1;
+            data_len += 1;
         }
 
+        ret.build_rows(data_len);
+        ret.validate_structure()?;
+        Ok(ret)
+    }
+
+    /// Lays out all the rows of a blank worksheet for the given (padded) data length
+    fn build_rows(&mut self, data_len: usize) {
         // Number of non-global-residue pairs of rows
         let n_rows = data_len / 2 + 1;
 
+        self.rows.clear();
         // Treat first two rows specially
-        ret.add_first_row();
-        ret.add_second_row();
+        self.add_first_row();
+        self.add_second_row();
         // Then do the remaining rows except the global SECRETSHARE32 residue
         for i in 0..n_rows - 1 {
-            ret.add_2nth_rows(2 * i);
+            self.add_2nth_rows(2 * i);
         }
         // Finally stick the global residue row on
-        ret.add_final_row();
+        self.add_final_row();
+        // The padding cell is never input, so compute whatever it implies now
+        if self.padded {
+            self.propagate(0, 0, &mut vec![]);
+        }
+    }
+
+    /// Clones the worksheet with a new share index, renumbering every cell's DOM id
+    pub fn with_index(&self, new_idx: usize) -> Worksheet {
+        let mut ret = self.clone();
+        ret.idx = new_idx;
+        for (ridx, row) in ret.rows.iter_mut().enumerate() {
+            for (cidx, cell) in row.cells.iter_mut().enumerate() {
+                cell.dom_id = format!("inp_{}_{}_{}", new_idx, ridx, cidx);
+            }
+        }
+        ret
+    }
 
+    /// Builds a worksheet of a different size, carrying over the entered share data
+    ///
+    /// Input characters are kept at the same share positions, except for any
+    /// which no longer fit or which are computed in the new worksheet.
+    pub fn resize(&self, size: usize) -> Result<Worksheet, Error> {
+        let mut ret = Worksheet::new(&self.hrp, size, self.checksum, self.idx)?;
+        ret.set_mode(self.mode);
+        let inputs: Vec<(usize, Fe)> = self
+            .share_data_indices()
+            .enumerate()
+            .filter(|&(_, (ridx, cidx))| !self.is_computed(ridx, cidx))
+            .filter_map(|(pos, (ridx, cidx))| Some((pos, self.rows[ridx].cells[cidx].val?)))
+            .collect();
+        for (pos, fe) in inputs {
+            if let Some((ridx, cidx)) = ret.position_to_cell(pos) {
+                if ret.is_editable(&ret.rows[ridx].cells[cidx]) {
+                    ret.write_input(ridx, cidx, Some(fe));
+                }
+            }
+        }
+        ret.recompute_all();
         Ok(ret)
     }
 
+    /// Blanks every cell of the worksheet, including fixed ones
+    ///
+    /// This is used to wipe share data before dropping a worksheet. The worksheet
+    /// should not be used afterward.
+    pub fn wipe(&mut self) {
+        for row in &mut self.rows {
+            for cell in &mut row.cells {
+                cell.val = None;
+            }
+        }
+    }
+
+    /// Whether the worksheet has no rows and must be reconstructed before use
+    ///
+    /// This can only happen to a partially-deserialized worksheet.
+    pub fn needs_reconstruction(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Rebuilds the rows of a worksheet which needs reconstruction
+    ///
+    /// Does nothing if the worksheet already has rows.
+    pub fn reconstruct(&mut self) -> Result<(), Error> {
+        if self.needs_reconstruction() {
+            let minimum = self.hrp.len() + 1 + self.checksum.len();
+            if self.size < minimum {
+                return Err(Error::TooShort {
+                    minimum,
+                    actual: self.size,
+                });
+            }
+            self.build_rows(self.size - minimum);
+            if self.mode != CreateMode::Create {
+                self.set_mode(self.mode);
+            }
+            self.validate_structure()?;
+        }
+        Ok(())
+    }
+
+    /// Checks the internal consistency of the worksheet layout
+    pub fn validate_structure(&self) -> Result<(), Error> {
+        let k = self.checksum.len();
+        let data_len = self.size - self.hrp.len() - k - 1;
+        if self.rows.len() != data_len + 3 {
+            return Err(Error::BadStructure {
+                row: self.rows.len(),
+                reason: "wrong number of rows for size",
+            });
+        }
+
+        for (ridx, row) in self.rows.iter().enumerate() {
+            let is_sum = ridx > 0 && ridx < self.rows.len() - 1 && ridx % 2 == 0;
+            let expected_len = if is_sum { k + 2 } else { k };
+            if row.cells.len() != expected_len {
+                return Err(Error::BadStructure {
+                    row: ridx,
+                    reason: "wrong number of cells",
+                });
+            }
+
+            let mut seen_checksum = false;
+            for (cidx, cell) in row.cells.iter().enumerate() {
+                if seen_checksum && !cell.is_checksum {
+                    return Err(Error::BadStructure {
+                        row: ridx,
+                        reason: "checksum cells not contiguous",
+                    });
+                }
+                seen_checksum = cell.is_checksum;
+
+                if let Some(below) = self.cell_below(ridx, cidx) {
+                    if self.cell_above(below.0, below.1) != Some((ridx, cidx)) {
+                        return Err(Error::BadStructure {
+                            row: ridx,
+                            reason: "cell_above does not invert cell_below",
+                        });
+                    }
+                }
+                if let Some(above) = self.cell_above(ridx, cidx) {
+                    if self.cell_below(above.0, above.1) != Some((ridx, cidx)) {
+                        return Err(Error::BadStructure {
+                            row: ridx,
+                            reason: "cell_below does not invert cell_above",
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Helper to construct a cell
     fn add_cell_to_last_row(&mut self, offset: usize, ty: CellType, val: Option<Fe>) {
         let ridx = self.rows.len() - 1;
@@ -190,6 +616,8 @@ impl Worksheet {
             is_checksum: self.hrp.len() + offset + 1 + cidx >= self.size - self.checksum.len(),
             dom_id: format!("inp_{}_{}_{}", self.idx, ridx, cidx),
             val,
+            seq: 0,
+            overridden: false,
         });
     }
 
@@ -197,7 +625,10 @@ impl Worksheet {
     fn add_first_row(&mut self) {
         assert_eq!(self.rows.len(), 0);
         self.rows.push(Row { cells: vec![] });
-        for _ in 0..self.checksum.len() {
+        if self.padded {
+            self.add_cell_to_last_row(0, CellType::Padding, Some(Fe::zero()));
+        }
+        while self.rows[0].cells.len() < self.checksum.len() {
             self.add_cell_to_last_row(0, CellType::ShareData, None);
         }
     }
@@ -206,15 +637,23 @@ impl Worksheet {
     fn add_second_row(&mut self) {
         assert_eq!(self.rows.len(), 1);
         self.rows.push(Row { cells: vec![] });
-        let hrp_poly = match self.checksum {
-            Checksum::Bech32 => fe::Poly::bech32_hrp_residue(&self.hrp),
-            Checksum::Codex32 => fe::Poly::codex32_hrp_residue(&self.hrp),
-        };
-        for fe in hrp_poly.iter() {
+        for fe in self.hrp_residue().iter() {
             self.add_cell_to_last_row(0, CellType::Residue, Some(fe));
         }
     }
 
+    /// The fixed contents of the second row, the residue of the HRP
+    fn hrp_residue(&self) -> fe::Poly {
+        // If there is a padding cell, the HRP is followed by one fewer data
+        // character in the first row, so it gets shifted one fewer place.
+        let shift = if self.padded {
+            self.checksum.len() - 1
+        } else {
+            self.checksum.len()
+        };
+        fe::Poly::hrp_residue_cached(&self.hrp, self.checksum, shift)
+    }
+
     /// Helper to populate the nth and (n+1)th "ordinary" rows (sum then residue)
     fn add_2nth_rows(&mut self, offset: usize) {
         self.rows.push(Row { cells: vec![] });
@@ -231,65 +670,467 @@ impl Worksheet {
     }
 
     fn add_final_row(&mut self) {
-        let checksum_str = match self.checksum {
-            Checksum::Codex32 => "SECRETSHARE32",
-            Checksum::Bech32 => "QQQQQP",
-        };
         self.rows.push(Row { cells: vec![] });
-        for ch in checksum_str.chars() {
-            self.add_cell_to_last_row(0, CellType::GlobalResidue, Fe::try_from(ch).ok());
+        for fe in self.checksum.target_residue().iter() {
+            self.add_cell_to_last_row(0, CellType::GlobalResidue, Some(fe));
         }
     }
 
     /// Constructs a giant array of cells with information to populate the DOM with
-    pub fn get_dom_cells(&self) -> Result<Vec<DomCell>, JsError> {
-        let mut ret = vec![]; // FIXME compute length
-        if self.rows.is_empty() {
-            return Ok(ret);
+    ///
+    /// If `numeric` is set, cell values are rendered as their decimal field value
+    /// rather than as bech32 characters.
+    pub fn get_dom_cells(&self, numeric: bool) -> Result<Vec<DomCell>, JsError> {
+        self.get_dom_cells_with_layout(numeric, Layout::Diagonal)
+    }
+
+    /// As `get_dom_cells`, but with the cells arranged according to `layout`
+    ///
+    /// Only the `x` and `y` coordinates depend on the layout.
+    pub fn get_dom_cells_with_layout(
+        &self,
+        numeric: bool,
+        layout: Layout,
+    ) -> Result<Vec<DomCell>, JsError> {
+        Ok(self
+            .laid_out_cell_data(numeric, layout)
+            .into_iter()
+            .map(From::from)
+            .collect())
+    }
+
+    /// The cell data from `dom_cell_data`, with coordinates for the given layout
+    fn laid_out_cell_data(&self, numeric: bool, layout: Layout) -> Vec<DomCellData> {
+        let mut ret = self.dom_cell_data(numeric);
+        if layout == Layout::Transposed {
+            for cell in &mut ret {
+                std::mem::swap(&mut cell.x, &mut cell.y);
+            }
+        }
+        ret
+    }
+
+    /// The `(x, y)` coordinates of the separator in the `get_dom_cells` layout
+    ///
+    /// The separator immediately follows the HRP on the first row.
+    pub fn separator_position(&self) -> (usize, usize) {
+        (self.hrp.len(), 0)
+    }
+
+    /// Outputs the same cells as `get_dom_cells` as a JSON array
+    pub fn dom_cells_json(&self, numeric: bool) -> Result<String, Error> {
+        serde_json::to_string(&self.dom_cell_data(numeric))
+            .map_err(|e| Error::Json { msg: e.to_string() })
+    }
+
+    /// Outputs every worksheet cell as CSV, one `row,col,type,value` line per cell
+    ///
+    /// Unlike `get_dom_cells`, this has no HRP or symbol cells, and positions are
+    /// (row, cell) indices rather than layout coordinates. Blank cells have an empty
+    /// value.
+    pub fn to_csv(&self) -> String {
+        if self.needs_reconstruction() {
+            let mut rebuilt = self.clone();
+            if rebuilt.reconstruct().is_ok() {
+                return rebuilt.to_csv();
+            }
+        }
+
+        let mut ret = String::from("row,col,type,value\n");
+        for (ridx, row) in self.rows.iter().enumerate() {
+            for (cidx, cell) in row.cells.iter().enumerate() {
+                ret.push_str(&format!(
+                    "{},{},{},{}\n",
+                    ridx,
+                    cidx,
+                    cell.ty.kind(cell.is_checksum).as_str(),
+                    cell.val
+                        .map(char::from)
+                        .map(String::from)
+                        .unwrap_or_default(),
+                ));
+            }
+        }
+        ret
+    }
+
+    /// Constructs a giant array of plain cell data, which `get_dom_cells` converts for the DOM
+    fn dom_cell_data(&self, numeric: bool) -> Vec<DomCellData> {
+        if self.needs_reconstruction() {
+            let mut rebuilt = self.clone();
+            // A worksheet which was valid when serialized will always reconstruct
+            return match rebuilt.reconstruct() {
+                Ok(()) => rebuilt.dom_cell_data(numeric),
+                Err(_) => vec![],
+            };
         }
 
+        let mut ret = vec![]; // FIXME compute length
+
         let mut offset = self.hrp.len();
         for (ny, row) in self.rows.iter().enumerate() {
             if ny == 0 {
                 for (n, ch) in self.hrp.chars().enumerate() {
-                    ret.push(DomCell {
-                        ty: "fixed_hrp",
+                    ret.push(DomCellData {
+                        ty: DomCellKind::FixedHrp,
                         dom_id: format!("cell_hrp_{}", n),
+                        text: ch.to_string(),
                         val: Some(ch),
                         x: n,
                         y: ny,
+                        editable: false,
                     });
                 }
-                ret.push(DomCell {
-                    ty: "fixed_hrp",
+                let (x, y) = self.separator_position();
+                ret.push(DomCellData {
+                    ty: DomCellKind::FixedHrp,
                     dom_id: format!("cell_hrp_{}", self.hrp.len()),
-                    val: Some('1'),
-                    x: self.hrp.len(),
-                    y: ny,
+                    text: self.checksum.separator_char().to_string(),
+                    val: Some(self.checksum.separator_char()),
+                    x,
+                    y,
+                    editable: false,
                 });
             } else {
                 if ny > 2 && ny % 2 == 1 {
                     offset += 2;
                 }
-                ret.push(DomCell {
-                    ty: "symbol",
+                let symb = if ny % 2 == 0 { '=' } else { '+' };
+                ret.push(DomCellData {
+                    ty: DomCellKind::Symbol,
                     dom_id: format!("cell_symb_{}", ny),
-                    val: Some(if ny % 2 == 0 { '=' } else { '+' }),
+                    text: symb.to_string(),
+                    val: Some(symb),
                     x: offset,
                     y: ny,
+                    editable: false,
                 });
             }
             for (nx, cell) in row.cells.iter().enumerate() {
-                ret.push(DomCell {
-                    ty: cell.ty.text(cell.is_checksum),
+                let text = match cell.val {
+                    Some(fe) if numeric => fe.to_numeric_string(),
+                    Some(fe) => char::from(fe).to_string(),
+                    None => String::new(),
+                };
+                ret.push(DomCellData {
+                    ty: cell.ty.kind(self.flows_up(cell)),
                     dom_id: cell.dom_id.clone(),
+                    text,
                     val: cell.val.map(|fe| fe.into()),
                     x: offset + 1 + nx,
                     y: ny,
+                    editable: self.is_editable(cell),
                 });
             }
         }
-        Ok(ret)
+        ret
+    }
+
+    /// Renders the worksheet as one string per row, laid out as in `get_dom_cells`,
+    /// with `.` for empty cells
+    #[cfg(test)]
+    pub fn row_strings(&self) -> Vec<String> {
+        let mut ret: Vec<Vec<char>> = vec![];
+        for cell in self.dom_cell_data(false) {
+            if ret.len() <= cell.y {
+                ret.resize(cell.y + 1, vec![]);
+            }
+            let row = &mut ret[cell.y];
+            if row.len() <= cell.x {
+                row.resize(cell.x + 1, ' ');
+            }
+            row[cell.x] = cell.val.unwrap_or('.');
+        }
+        ret.into_iter()
+            .map(|row| row.into_iter().collect())
+            .collect()
+    }
+
+    /// Renders the worksheet as a single multi-line string (see `row_strings`)
+    #[cfg(test)]
+    pub fn as_ascii_art(&self) -> String {
+        self.row_strings().join("\n")
+    }
+
+    /// Sets whether lowercase input is corrected with a `flash_set` (the default)
+    /// or silently with a plain `set`
+    pub fn set_flash_case(&mut self, flash_case: bool) {
+        self.flash_case = flash_case;
+    }
+
+    /// Turns recording of emitted actions into the action history on or off
+    ///
+    /// Turning recording off does not clear the history.
+    pub fn record_actions(&mut self, on: bool) {
+        self.recording_actions = on;
+    }
+
+    /// Every action emitted in response to input while recording was on
+    pub fn action_history(&self) -> &[Action] {
+        &self.action_history
+    }
+
+    /// Empties the action history
+    pub fn clear_action_history(&mut self) {
+        self.action_history.clear();
+    }
+
+    /// Appends actions to the action history, if recording
+    fn record_actions_from(&mut self, actions: &[Action]) {
+        if self.recording_actions {
+            self.action_history.extend_from_slice(actions);
+        }
+    }
+
+    /// The share index used in the worksheet's DOM ids
+    pub fn idx(&self) -> usize {
+        self.idx
+    }
+
+    /// The mode of the worksheet
+    pub fn mode(&self) -> CreateMode {
+        self.mode
+    }
+
+    /// The checksum used by the worksheet
+    pub fn checksum(&self) -> Checksum {
+        self.checksum
+    }
+
+    /// Whether updates to this cell propagate up the worksheet rather than down
+    ///
+    /// This is the case for the checksum cells in create mode, which are computed
+    /// backward from the fixed global residue.
+    fn flows_up(&self, cell: &Cell) -> bool {
+        self.mode == CreateMode::Create && cell.is_checksum
+    }
+
+    /// Whether a cell accepts direct user input
+    fn is_editable(&self, cell: &Cell) -> bool {
+        match self.mode {
+            CreateMode::Blank => cell.ty != CellType::Padding,
+            _ => cell.ty == CellType::ShareData && !self.flows_up(cell),
+        }
+    }
+
+    /// Whether the value of a cell is computed from other cells, rather than being
+    /// input by the user or fixed
+    fn is_computed(&self, ridx: usize, cidx: usize) -> bool {
+        if self.mode == CreateMode::Blank {
+            return false;
+        }
+        let cell = &self.rows[ridx].cells[cidx];
+        match cell.ty {
+            CellType::ShareData => self.flows_up(cell),
+            // The HRP residue is fixed
+            CellType::Residue => ridx != 1,
+            CellType::Sum => true,
+            CellType::GlobalResidue => self.mode == CreateMode::Verify,
+            CellType::Padding => false,
+        }
+    }
+
+    /// Whether the value of a cell is determined by other cells
+    ///
+    /// This is `is_computed`, except in blank mode, where the cells that would
+    /// otherwise be computed are input by the user but still have a correct value.
+    fn is_derived(&self, ridx: usize, cidx: usize) -> bool {
+        match self.mode {
+            CreateMode::Blank => matches!(
+                self.rows[ridx].cells[cidx].ty,
+                CellType::Residue | CellType::Sum | CellType::GlobalResidue
+            ),
+            _ => self.is_computed(ridx, cidx),
+        }
+    }
+
+    /// Blanks every computed cell, including any overridden ones
+    fn clear_computed(&mut self) {
+        for ridx in 0..self.rows.len() {
+            for cidx in 0..self.rows[ridx].cells.len() {
+                if self.is_computed(ridx, cidx) {
+                    self.rows[ridx].cells[cidx].val = None;
+                    self.rows[ridx].cells[cidx].overridden = false;
+                }
+            }
+        }
+    }
+
+    /// Switches the worksheet to a new mode, blanking all computed cells
+    ///
+    /// Any overrides are dropped. In create mode the global residue is reset to its fixed value. In
+    /// blank mode every residue and sum, including the HRP residue and the global residue, is
+    /// blanked instead. Call `recompute_all` to fill the computed cells back in.
+    pub fn set_mode(&mut self, mode: CreateMode) {
+        self.mode = mode;
+        self.clear_computed();
+        if mode == CreateMode::Blank {
+            for row in &mut self.rows {
+                for cell in &mut row.cells {
+                    if matches!(
+                        cell.ty,
+                        CellType::Residue | CellType::Sum | CellType::GlobalResidue
+                    ) {
+                        cell.val = None;
+                        cell.overridden = false;
+                    }
+                }
+            }
+            return;
+        }
+        let hrp_residue = self.hrp_residue();
+        for (cell, fe) in self.rows[1].cells.iter_mut().zip(hrp_residue.iter()) {
+            cell.val = Some(fe);
+        }
+        if mode == CreateMode::Create {
+            let final_row = self.rows.last_mut().unwrap();
+            for (cell, fe) in final_row
+                .cells
+                .iter_mut()
+                .zip(self.checksum.target_residue().iter())
+            {
+                cell.val = Some(fe);
+            }
+        }
+    }
+
+    /// Recomputes every computed cell from the input cells
+    ///
+    /// Returns a list of actions in the order a user would fill in the cells.
+    pub fn recompute_all(&mut self) -> Vec<Action> {
+        let mut ret = vec![];
+        if self.padded {
+            self.propagate(0, 0, &mut ret);
+        }
+        let inputs: Vec<_> = self
+            .share_data_indices()
+            .filter(|&(ridx, cidx)| !self.is_computed(ridx, cidx))
+            .collect();
+        for (ridx, cidx) in inputs {
+            self.propagate(ridx, cidx, &mut ret);
+        }
+        self.record_actions_from(&ret);
+        ret
+    }
+
+    /// Blanks every computed cell, keeping the user's input, e.g. if the computed
+    /// cells may be stale
+    ///
+    /// Unlike `set_mode`, overridden cells are kept. Returns actions to blank the
+    /// cells; call `recompute_all` to fill them back in.
+    pub fn reset_computed(&mut self) -> Vec<Action> {
+        let mut ret = vec![];
+        for ridx in 0..self.rows.len() {
+            for cidx in 0..self.rows[ridx].cells.len() {
+                if self.is_computed(ridx, cidx) {
+                    self.set_computed(ridx, cidx, None, &mut ret);
+                }
+            }
+        }
+        self.record_actions_from(&ret);
+        ret
+    }
+
+    /// The value a computed cell should have, given the currently-entered data
+    ///
+    /// Returns `None` for input cells, for out-of-range indices, or if the data the
+    /// cell depends on has not all been entered. Does not modify the worksheet.
+    pub fn expected_value(&self, ridx: usize, cidx: usize) -> Option<Fe> {
+        let cell = self.rows.get(ridx)?.cells.get(cidx)?;
+        if matches!(cell.ty, CellType::ShareData | CellType::Padding)
+            && !self.is_derived(ridx, cidx)
+        {
+            return None;
+        }
+        self.recomputed().rows[ridx].cells[cidx].val
+    }
+
+    /// The DOM ids of computed cells whose values contradict the entered data
+    ///
+    /// Only cells whose correct value can be determined from the data entered so
+    /// far are checked, so this works on incomplete worksheets.
+    pub fn check_consistency(&self) -> Vec<String> {
+        let scratch = self.recomputed();
+        let mut ret = vec![];
+        for (ridx, (row, expected_row)) in self.rows.iter().zip(&scratch.rows).enumerate() {
+            for (cidx, (cell, expected)) in row.cells.iter().zip(&expected_row.cells).enumerate() {
+                if !self.is_derived(ridx, cidx) {
+                    continue;
+                }
+                if let (Some(val), Some(expected)) = (cell.val, expected.val) {
+                    if val != expected {
+                        ret.push(cell.dom_id.clone());
+                    }
+                }
+            }
+        }
+        ret
+    }
+
+    /// The DOM ids of data cells which look like they were entered one row off
+    ///
+    /// Because of the diagonal layout, a pair of data characters is easily written
+    /// into the row above or below the one it belongs in. This is a heuristic: two
+    /// neighboring pairs are flagged if the worksheet has inconsistent cells or an
+    /// invalid global residue, and exchanging the pairs would fix all of them.
+    pub fn detect_misaligned_entry(&self) -> Vec<String> {
+        fn n_mistakes(worksheet: &Worksheet) -> usize {
+            let invalid = worksheet.global_residue_status() == GlobalResidueStatus::Invalid;
+            worksheet.check_consistency().len() + usize::from(invalid)
+        }
+        if n_mistakes(self) == 0 {
+            return vec![];
+        }
+
+        let k = self.checksum.len();
+        let pairs: Vec<usize> = (2..self.rows.len() - 1)
+            .step_by(2)
+            .filter(|&ridx| {
+                self.rows[ridx].cells[k..]
+                    .iter()
+                    .all(|cell| cell.val.is_some() && self.is_editable(cell))
+            })
+            .collect();
+        let mut ret = vec![];
+        for window in pairs.windows(2) {
+            let (upper, lower) = (window[0], window[1]);
+            // Only directly neighboring rows with different data are considered
+            let same_data = (k..k + 2)
+                .all(|cidx| self.rows[upper].cells[cidx].val == self.rows[lower].cells[cidx].val);
+            if lower != upper + 2 || same_data {
+                continue;
+            }
+            let mut scratch = self.clone();
+            for cidx in k..k + 2 {
+                let val = scratch.rows[upper].cells[cidx].val;
+                scratch.rows[upper].cells[cidx].val = scratch.rows[lower].cells[cidx].val;
+                scratch.rows[lower].cells[cidx].val = val;
+            }
+            scratch.recompute_all();
+            if n_mistakes(&scratch) == 0 {
+                for ridx in [upper, lower] {
+                    for cell in &self.rows[ridx].cells[k..] {
+                        if !ret.contains(&cell.dom_id) {
+                            ret.push(cell.dom_id.clone());
+                        }
+                    }
+                }
+            }
+        }
+        ret
+    }
+
+    /// A copy of the worksheet with every computed cell recomputed from scratch
+    ///
+    /// A worksheet in blank mode is recomputed as in verify mode.
+    fn recomputed(&self) -> Worksheet {
+        let mut ret = self.clone();
+        ret.set_mode(match self.mode {
+            CreateMode::Blank => CreateMode::Verify,
+            mode => mode,
+        });
+        ret.recompute_all();
+        ret
     }
 
     /// Helper function to offset a ridx/cidx pair
@@ -312,8 +1153,9 @@ impl Worksheet {
         if ridx == 0 {
             return None;
         }
-        // No more rows in this column
-        let offset_adj = 2 * (ridx % 2);
+        // No more rows in this column (the HRP residue row is not offset
+        // from the row above it, unlike every later residue row)
+        let offset_adj = if ridx > 1 && ridx % 2 == 1 { 2 } else { 0 };
         if cidx + offset_adj >= self.rows[ridx - 1].cells.len() {
             return None;
         }
@@ -327,39 +1169,195 @@ impl Worksheet {
         cidx: usize,
         val: &str,
     ) -> Result<Vec<Action>, Error> {
-        if ridx >= self.rows.len() {
-            return Err(Error::InvalidRow {
-                row: ridx,
-                n_rows: self.rows.len(),
-            });
-        }
-        if cidx >= self.rows[ridx].cells.len() {
-            return Err(Error::InvalidCell {
+        self.check_input_cell(ridx, cidx)?;
+        let mut ret = vec![];
+        if self.set_input(ridx, cidx, val, &mut ret) {
+            // Actually update the sheet
+            self.propagate(ridx, cidx, &mut ret);
+            self.flag_mistake(ridx, cidx, &mut ret);
+            // After a character is entered, move on to the next blank cell
+            let cell = &self.rows[ridx].cells[cidx];
+            if cell.ty == CellType::ShareData && cell.val.is_some() {
+                if let Some((next_r, next_c)) = self.next_empty_cell(ridx, cidx) {
+                    ret.push(Action {
+                        ty: "focus",
+                        id: self.rows[next_r].cells[next_c].dom_id.clone(),
+                        value: None,
+                    });
+                }
+            }
+        }
+        self.record_actions_from(&ret);
+        Ok(ret)
+    }
+
+    /// Handles several user inputs at once, e.g. from a paste
+    ///
+    /// Propagation runs once after all the inputs have been set, and only the final
+    /// `set` action for each cell is returned. If any cell is invalid, no input is
+    /// applied.
+    pub fn handle_input_batch(
+        &mut self,
+        changes: &[(usize, usize, &str)],
+    ) -> Result<Vec<Action>, Error> {
+        self.check_input_batch(changes)?;
+
+        let mut ret = vec![];
+        let mut changed = Vec::with_capacity(changes.len());
+        for &(ridx, cidx, val) in changes {
+            if self.set_input(ridx, cidx, val, &mut ret) {
+                changed.push((ridx, cidx));
+            }
+        }
+        self.propagate_from(&changed, &mut ret);
+        for &(ridx, cidx) in &changed {
+            self.flag_mistake(ridx, cidx, &mut ret);
+        }
+
+        // Drop all but the last `set` of each cell
+        let mut seen = HashSet::new();
+        let mut deduped: Vec<Action> = ret
+            .into_iter()
+            .rev()
+            .filter(|action| action.ty != "set" || seen.insert(action.id.clone()))
+            .collect();
+        deduped.reverse();
+        self.record_actions_from(&deduped);
+        Ok(deduped)
+    }
+
+    /// Checks that every cell of a batch accepts user input, without applying it
+    pub fn check_input_batch(&self, changes: &[(usize, usize, &str)]) -> Result<(), Error> {
+        for &(ridx, cidx, _) in changes {
+            self.check_input_cell(ridx, cidx)?;
+        }
+        Ok(())
+    }
+
+    /// A human-readable name for a row, as a user would see it on the worksheet
+    fn row_label(&self, ridx: usize) -> String {
+        match ridx {
+            0 => "share data".into(),
+            1 => "HRP residue".into(),
+            _ if ridx >= self.rows.len() => "past the global residue".into(),
+            _ if ridx == self.rows.len() - 1 => "global residue".into(),
+            _ if ridx % 2 == 0 => format!("sum {}", ridx / 2),
+            _ => format!("residue {}", ridx / 2),
+        }
+    }
+
+    /// A human-readable description of how each row is computed, for explaining the
+    /// worksheet to the user
+    ///
+    /// Each hint starts with the row's label, as used by `cell_description`.
+    pub fn render_hints(&self) -> Vec<String> {
+        let last = self.rows.len() - 1;
+        (0..self.rows.len())
+            .map(|ridx| {
+                let how = match ridx {
+                    0 => format!(
+                        "the first {} characters of the share data",
+                        self.rows[0].cells.len()
+                    ),
+                    1 => format!(
+                        "the residue of the HRP {:?} under the {:?} checksum",
+                        self.hrp, self.checksum
+                    ),
+                    2 => "the share data plus the HRP residue, followed by the next two share \
+                          characters"
+                        .into(),
+                    _ if ridx == last => format!(
+                        "{} plus {} shifted left two places, which must equal {}",
+                        self.row_label(ridx - 1),
+                        self.row_label(ridx - 2),
+                        self.checksum.target_residue()
+                    ),
+                    _ if ridx % 2 == 0 => format!(
+                        "{} plus {} shifted left two places, followed by the next two share \
+                         characters",
+                        self.row_label(ridx - 1),
+                        self.row_label(ridx - 2)
+                    ),
+                    _ => format!(
+                        "the reduction of the first two characters of {} by the {:?} checksum",
+                        self.row_label(ridx - 1),
+                        self.checksum
+                    ),
+                };
+                format!("{}: {}", self.row_label(ridx), how)
+            })
+            .collect()
+    }
+
+    /// A human-readable description of a cell, e.g. "HRP residue, column 3"
+    pub fn cell_description(&self, ridx: usize, cidx: usize) -> String {
+        format!("{}, column {}", self.row_label(ridx), cidx)
+    }
+
+    /// Checks that a cell exists and accepts user input (padding cells are allowed,
+    /// but the input will be rejected with a `flash_error`)
+    fn check_input_cell(&self, ridx: usize, cidx: usize) -> Result<(), Error> {
+        if ridx >= self.rows.len() {
+            return Err(Error::InvalidRow {
+                row: ridx,
+                n_rows: self.rows.len(),
+                location: self.row_label(ridx),
+            });
+        }
+        if cidx >= self.rows[ridx].cells.len() {
+            return Err(Error::InvalidCell {
                 cell: cidx,
                 row: ridx,
-                n_cells: self.rows[0].cells.len(),
+                n_cells: self.rows[ridx].cells.len(),
+                location: self.cell_description(ridx, cidx),
             });
         }
 
-        let mut ret = vec![];
+        let cell = &self.rows[ridx].cells[cidx];
+        if cell.ty != CellType::Padding && !self.is_editable(cell) {
+            return Err(Error::NotEditable {
+                row: ridx,
+                cell: cidx,
+            });
+        }
+        Ok(())
+    }
+
+    /// Sets the value of an input cell from user input, without propagating it
+    ///
+    /// Returns whether a value was written, and so needs to be propagated. Rejected
+    /// input flashes an error and leaves the cell unchanged.
+    fn set_input(&mut self, ridx: usize, cidx: usize, val: &str, ret: &mut Vec<Action>) -> bool {
+        if self.rows[ridx].cells[cidx].ty == CellType::Padding {
+            ret.push(Action {
+                ty: "flash_error",
+                id: self.rows[ridx].cells[cidx].dom_id.clone(),
+                value: None,
+            });
+            return false;
+        }
+
         match val.len() {
-            0 => self.rows[ridx].cells[cidx].val = None,
+            // Clearing is an input like any other, so the caller's propagation
+            // blanks every cell computed from this one
+            0 => self.write_input(ridx, cidx, None),
             1 => {
                 if !val.is_ascii() {
-                    return Ok(vec![Action {
+                    ret.push(Action {
                         ty: "flash_error",
                         id: self.rows[ridx].cells[cidx].dom_id.clone(),
                         value: None,
-                    }]);
+                    });
+                    return false;
                 }
                 let ch = val.chars().next().unwrap();
                 let ch_u = ch.to_ascii_uppercase();
                 match Fe::try_from(ch_u) {
                     Ok(fe) => {
-                        self.rows[ridx].cells[cidx].val = Some(fe);
+                        self.write_input(ridx, cidx, Some(fe));
                         if ch != ch_u {
                             ret.push(Action {
-                                ty: "flash_set",
+                                ty: if self.flash_case { "flash_set" } else { "set" },
                                 id: self.rows[ridx].cells[cidx].dom_id.clone(),
                                 value: Some(ch_u),
                             });
@@ -371,19 +1369,71 @@ impl Worksheet {
                             id: self.rows[ridx].cells[cidx].dom_id.clone(),
                             value: None,
                         });
+                        return false;
                     }
                 };
             }
-            _ => ret.push(Action {
-                ty: "flash_error",
-                id: self.rows[ridx].cells[cidx].dom_id.clone(),
-                value: None,
-            }),
+            _ => {
+                ret.push(Action {
+                    ty: "flash_error",
+                    id: self.rows[ridx].cells[cidx].dom_id.clone(),
+                    value: None,
+                });
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Sets the value of a cell from user input, stamping it with a new revision
+    fn write_input(&mut self, ridx: usize, cidx: usize, val: Option<Fe>) {
+        self.revision += 1;
+        let cell = &mut self.rows[ridx].cells[cidx];
+        cell.val = val;
+        cell.seq = self.revision;
+    }
+
+    /// The number of the latest user input to the worksheet, or 0 if there was none
+    ///
+    /// Every input is stamped with a new, higher revision, so when merging two
+    /// copies of a worksheet the more recently input value of a cell can be chosen.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// In blank mode, flashes an error on a residue or sum cell whose entered value
+    /// contradicts the data entered so far
+    ///
+    /// The value is kept, so the user can see what they entered.
+    fn flag_mistake(&self, ridx: usize, cidx: usize, ret: &mut Vec<Action>) {
+        if self.mode != CreateMode::Blank || !self.is_derived(ridx, cidx) {
+            return;
         }
+        let cell = &self.rows[ridx].cells[cidx];
+        if let (Some(val), Some(expected)) = (cell.val, self.expected_value(ridx, cidx)) {
+            if val != expected {
+                ret.push(Action {
+                    ty: "flash_error",
+                    id: cell.dom_id.clone(),
+                    value: None,
+                });
+            }
+        }
+    }
 
-        // Actually update the sheet
-        let mut queue = VecDeque::with_capacity(2 * self.checksum.len());
-        queue.push_back((ridx, cidx));
+    /// Recursively update all cells implied by a change to the given cell
+    fn propagate(&mut self, ridx: usize, cidx: usize, ret: &mut Vec<Action>) {
+        self.propagate_from(&[(ridx, cidx)], ret)
+    }
+
+    /// Recursively update all cells implied by changes to the given cells
+    fn propagate_from(&mut self, cells: &[(usize, usize)], ret: &mut Vec<Action>) {
+        // In blank mode nothing is computed
+        if self.mode == CreateMode::Blank {
+            return;
+        }
+        let mut queue = VecDeque::with_capacity(2 * self.checksum.len() + cells.len());
+        queue.extend(cells.iter().copied());
 
         // Utility to add two possibly-set fes
         fn fe_add(fe1: Option<Fe>, fe2: Option<Fe>) -> Option<Fe> {
@@ -406,7 +1456,7 @@ impl Worksheet {
         // Recursively update all cells
         while let Some((ridx, cidx)) = queue.pop_front() {
             let cell = &self.rows[ridx].cells[cidx];
-            match (cell.ty, cell.is_checksum) {
+            match (cell.ty, self.flows_up(cell)) {
                 (CellType::Sum, _) if cidx == 0 || cidx == 1 => {
                     // For lower diagonal cells, try to compute a residue:
                     assert!(self.rows[ridx].cells.len() > 1);
@@ -419,142 +1469,711 @@ impl Worksheet {
                         poly.mul_by_x_then_add(fe2);
                         poly.mul_by_x(self.checksum.len());
                         assert!(self.rows[ridx + 1].cells.len() >= self.checksum.len());
-                        let residue = match self.checksum {
-                            Checksum::Codex32 => poly.codex32_polymod(),
-                            Checksum::Bech32 => poly.bech32_polymod(),
-                        };
+                        let residue = poly.checksum_polymod(self.checksum);
                         // ...then put it into the next line's cells
                         for (n, fe) in residue.iter().enumerate() {
-                            if self.rows[ridx + 1].cells[n].val == Some(fe) {
-                                continue; // don't update if the cell is already set
+                            if self.set_computed(ridx + 1, n, Some(fe), ret) {
+                                queue.push_back((ridx + 1, n));
                             }
-                            self.rows[ridx + 1].cells[n].val = Some(fe);
-                            ret.push(Action {
-                                ty: "set",
-                                id: self.rows[ridx + 1].cells[n].dom_id.clone(),
-                                value: Some(fe.into()),
-                            });
-                            queue.push_back((ridx + 1, n));
                         }
                     } else {
                         // Otherwise blank the residue
                         for n in 0..self.checksum.len() {
-                            if self.rows[ridx + 1].cells[n].val == None {
-                                continue; // don't update if the cell is already blank
+                            if self.set_computed(ridx + 1, n, None, ret) {
+                                queue.push_back((ridx + 1, n));
                             }
-                            self.rows[ridx + 1].cells[n].val = None;
-                            ret.push(Action {
-                                ty: "set",
-                                id: self.rows[ridx + 1].cells[n].dom_id.clone(),
-                                value: None,
-                            });
-                            queue.push_back((ridx + 1, n));
                         }
                     }
                 }
-                (CellType::Sum, false) | (CellType::ShareData, false) => {
+                (CellType::Sum, false)
+                | (CellType::ShareData, false)
+                | (CellType::Padding, false) => {
                     // For sum cells, we try to add to the cell below
                     let below = unwrap_or_continue!(self.cell_below(ridx, cidx));
                     let below2 = unwrap_or_continue!(self.cell_below(below.0, below.1));
-                    // Update the sum, return to the JS an instruction to update the cell, and add it to the queue
+                    // Update the sum, and if it changed add it to the queue
                     let fe3 = fe_add(cell.val, self.rows[below.0].cells[below.1].val);
-                    if self.rows[below2.0].cells[below2.1].val == fe3 {
-                        continue; // don't update if the cell is already set
+                    if self.set_computed(below2.0, below2.1, fe3, ret) {
+                        queue.push_front(below2);
                     }
-                    self.rows[below2.0].cells[below2.1].val = fe3;
-                    ret.push(Action {
-                        ty: "set",
-                        id: self.rows[below2.0].cells[below2.1].dom_id.clone(),
-                        value: fe3.map(char::from),
-                    });
-                    queue.push_front((below2.0, below2.1));
                 }
-                (CellType::Sum, true) | (CellType::ShareData, true) => {
+                (CellType::Sum, true) | (CellType::ShareData, true) | (CellType::Padding, true) => {
                     // For sum cells, we try to add to the cell above
                     let above = unwrap_or_continue!(self.cell_above(ridx, cidx));
                     let above2 = unwrap_or_continue!(self.cell_above(above.0, above.1));
-                    // Update the sum, return to the JS an instruction to update the cell, and add it to the queue
+                    // Update the sum, and if it changed add it to the queue
                     let fe3 = fe_add(cell.val, self.rows[above.0].cells[above.1].val);
-                    if self.rows[above2.0].cells[above2.1].val == fe3 {
-                        continue; // don't update if the cell is already set
+                    if self.set_computed(above2.0, above2.1, fe3, ret) {
+                        queue.push_front(above2);
                     }
-                    self.rows[above2.0].cells[above2.1].val = fe3;
-                    ret.push(Action {
-                        ty: "set",
-                        id: self.rows[above2.0].cells[above2.1].dom_id.clone(),
-                        value: fe3.map(char::from),
-                    });
-                    queue.push_front((above2.0, above2.1));
                 }
                 (CellType::Residue, false) => {
                     // Residue cells are very similar to sum cells
                     let above = unwrap_or_continue!(self.cell_above(ridx, cidx));
                     let below = unwrap_or_continue!(self.cell_below(ridx, cidx));
-                    // Update the sum, return to the JS an instruction to update the cell, and add it to the queue
+                    // Update the sum, and if it changed add it to the queue
                     let fe3 = fe_add(cell.val, self.rows[above.0].cells[above.1].val);
-                    if self.rows[below.0].cells[below.1].val == fe3 {
-                        continue; // don't update if the cell is already set
+                    if self.set_computed(below.0, below.1, fe3, ret) {
+                        queue.push_front(below);
                     }
-                    self.rows[below.0].cells[below.1].val = fe3;
-                    ret.push(Action {
-                        ty: "set",
-                        id: self.rows[below.0].cells[below.1].dom_id.clone(),
-                        value: fe3.map(char::from),
-                    });
-                    queue.push_front((below.0, below.1));
                 }
                 (CellType::Residue, true) => {
                     // Residue cells are very similar to sum cells
                     let below = unwrap_or_continue!(self.cell_below(ridx, cidx));
                     let above = unwrap_or_continue!(self.cell_above(ridx, cidx));
-                    // Update the sum, return to the JS an instruction to update the cell, and add it to the queue
+                    // Update the sum, and if it changed add it to the queue
                     let fe3 = fe_add(cell.val, self.rows[below.0].cells[below.1].val);
-                    if self.rows[above.0].cells[above.1].val == fe3 {
-                        continue; // don't update if the cell is already set
+                    if self.set_computed(above.0, above.1, fe3, ret) {
+                        queue.push_back(above);
                     }
-                    self.rows[above.0].cells[above.1].val = fe3;
-                    ret.push(Action {
-                        ty: "set",
-                        id: self.rows[above.0].cells[above.1].dom_id.clone(),
-                        value: fe3.map(char::from),
-                    });
-                    queue.push_back((above.0, above.1));
                 }
-                (CellType::GlobalResidue, _) => unreachable!(),
+                // In verify mode the global residue is computed, but nothing depends on it
+                (CellType::GlobalResidue, _) => {}
             }
         }
+    }
+
+    /// The cells which `propagate` would directly recompute after a change to the
+    /// given cell
+    fn direct_dependents(&self, ridx: usize, cidx: usize) -> Vec<(usize, usize)> {
+        let cell = &self.rows[ridx].cells[cidx];
+        match (cell.ty, self.flows_up(cell)) {
+            (CellType::Sum, _) if cidx == 0 || cidx == 1 => {
+                (0..self.checksum.len()).map(|n| (ridx + 1, n)).collect()
+            }
+            (CellType::Sum, false) | (CellType::ShareData, false) | (CellType::Padding, false) => {
+                self.cell_below(ridx, cidx)
+                    .and_then(|(r, c)| self.cell_below(r, c))
+                    .into_iter()
+                    .collect()
+            }
+            (CellType::Sum, true) | (CellType::ShareData, true) | (CellType::Padding, true) => self
+                .cell_above(ridx, cidx)
+                .and_then(|(r, c)| self.cell_above(r, c))
+                .into_iter()
+                .collect(),
+            (CellType::Residue, false) => self.cell_below(ridx, cidx).into_iter().collect(),
+            (CellType::Residue, true) => self.cell_above(ridx, cidx).into_iter().collect(),
+            (CellType::GlobalResidue, _) => vec![],
+        }
+    }
+
+    /// Sets a computed cell, returning the JS an instruction to update it
+    ///
+    /// Returns whether the cell changed. Overridden cells are never changed.
+    fn set_computed(
+        &mut self,
+        ridx: usize,
+        cidx: usize,
+        val: Option<Fe>,
+        ret: &mut Vec<Action>,
+    ) -> bool {
+        let cell = &mut self.rows[ridx].cells[cidx];
+        if cell.overridden || cell.val == val {
+            return false;
+        }
+        cell.val = val;
+        ret.push(Action {
+            ty: "set",
+            id: cell.dom_id.clone(),
+            value: val.map(char::from),
+        });
+        true
+    }
+
+    /// Manually sets the value of a computed cell, and propagates it
+    ///
+    /// The cell keeps this value through `recompute_all` until `clear_override` is
+    /// called, and cells computed from it use the overridden value. Changing the mode
+    /// drops all overrides.
+    pub fn set_override(&mut self, id: &str, val: char) -> Result<Vec<Action>, Error> {
+        let (ridx, cidx) = self.find_cell(id)?;
+        if !self.is_computed(ridx, cidx) {
+            return Err(Error::UnknownCell {
+                id: id.into(),
+                reason: "not a computed cell",
+            });
+        }
+        let fe = Fe::try_from(val.to_ascii_uppercase())?;
+
+        let cell = &mut self.rows[ridx].cells[cidx];
+        cell.overridden = true;
+        cell.val = Some(fe);
+        let mut ret = vec![Action {
+            ty: "set",
+            id: id.into(),
+            value: Some(fe.into()),
+        }];
+        self.propagate(ridx, cidx, &mut ret);
+        self.record_actions_from(&ret);
+        Ok(ret)
+    }
+
+    /// Reverts an overridden cell to its computed value, and propagates it
+    ///
+    /// Does nothing if the cell is not overridden.
+    pub fn clear_override(&mut self, id: &str) -> Result<Vec<Action>, Error> {
+        let (ridx, cidx) = self.find_cell(id)?;
+        if !self.rows[ridx].cells[cidx].overridden {
+            return Ok(vec![]);
+        }
+        self.rows[ridx].cells[cidx].overridden = false;
+        let val = self.expected_value(ridx, cidx);
+
+        let mut ret = vec![];
+        self.set_computed(ridx, cidx, val, &mut ret);
+        self.propagate(ridx, cidx, &mut ret);
+        self.record_actions_from(&ret);
         Ok(ret)
     }
 
-    /// Returns the first six characters of the share, with `_`s for missing characters
-    /// characters of the share (the header)
+    /// Returns the header characters of the share, with `_`s for missing characters
+    ///
+    /// The length of the header is given by `Checksum::header_len`.
     pub fn header_str(&self) -> String {
+        let len = self.checksum.header_len();
         if self.rows.is_empty() {
-            "______".into()
+            "_".repeat(len)
         } else {
-            let mut ret = String::with_capacity(6);
-            let iter = self.rows[0].cells.iter().map(|cell| cell.val).take(6);
-            for fe in iter {
+            let mut ret = String::with_capacity(len);
+            for fe in self.share_data().take(len) {
                 ret.push(fe.map(From::from).unwrap_or('_'));
             }
-            while ret.len() < 6 {
+            while ret.len() < len {
                 ret.push('_');
             }
             ret
         }
     }
 
+    /// Iterates over the (row, cell) indices of the share data cells in share order,
+    /// skipping any padding
+    fn share_data_indices(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let k = self.checksum.len();
+        let first = (0..k).map(|cidx| (0, cidx));
+        // Every sum row ends in a pair of share data cells; the final row is not a sum row
+        let rest = (2..self.rows.len() - 1)
+            .step_by(2)
+            .flat_map(move |ridx| [(ridx, k), (ridx, k + 1)]);
+        first
+            .chain(rest)
+            .filter(|&(ridx, cidx)| self.rows[ridx].cells[cidx].ty == CellType::ShareData)
+    }
+
+    /// Iterates over the share data cells' values in share order, skipping any padding
+    fn share_data(&self) -> impl Iterator<Item = Option<Fe>> + '_ {
+        self.share_data_indices()
+            .map(|(ridx, cidx)| self.rows[ridx].cells[cidx].val)
+    }
+
+    /// The share data positions at which this worksheet differs from another, with
+    /// both values
+    ///
+    /// Both worksheets should have the same size, as do all shares in a session.
+    pub fn compare_share_data(&self, other: &Worksheet) -> Vec<ShareDataDiff> {
+        self.share_data()
+            .zip(other.share_data())
+            .enumerate()
+            .filter(|(_, (ours, theirs))| ours != theirs)
+            .map(|(pos, (ours, theirs))| (pos, ours.map(char::from), theirs.map(char::from)))
+            .collect()
+    }
+
+    /// The (row, cell) indices of the share data character at the given position
+    /// (after the HRP and separator)
+    ///
+    /// This is the single mapping between share positions and worksheet cells, used
+    /// for input, export and error correction alike.
+    pub fn position_to_cell(&self, pos: usize) -> Option<(usize, usize)> {
+        self.share_data_indices().nth(pos)
+    }
+
+    /// The share position of the cell at the given (row, cell) indices, or `None` if
+    /// it is not a share data cell
+    pub fn cell_to_position(&self, ridx: usize, cidx: usize) -> Option<usize> {
+        self.share_data_indices()
+            .position(|idx| idx == (ridx, cidx))
+    }
+
+    /// Sets the share data character at the given position (after the HRP and separator)
+    pub fn set_data_char(&mut self, pos: usize, ch: char) -> Result<Vec<Action>, Error> {
+        let (ridx, cidx) = self
+            .position_to_cell(pos)
+            .ok_or_else(|| Error::InvalidPosition {
+                pos,
+                n_positions: self.share_data_indices().count(),
+            })?;
+        self.handle_input_change(ridx, cidx, &ch.to_string())
+    }
+
+    /// Exchanges the values of two share data cells, identified by DOM id
+    ///
+    /// This is a one-step fix for a transposition. Returns the actions needed to
+    /// update both cells and everything computed from them.
+    pub fn swap_cells(&mut self, id_a: &str, id_b: &str) -> Result<Vec<Action>, Error> {
+        let (ra, ca) = self.find_cell(id_a)?;
+        let (rb, cb) = self.find_cell(id_b)?;
+        for &(ridx, cidx) in &[(ra, ca), (rb, cb)] {
+            if !self.is_editable(&self.rows[ridx].cells[cidx]) {
+                return Err(Error::NotEditable {
+                    row: ridx,
+                    cell: cidx,
+                });
+            }
+        }
+
+        let val_a = self.rows[ra].cells[ca].val;
+        let val_b = self.rows[rb].cells[cb].val;
+        self.write_input(ra, ca, val_b);
+        self.write_input(rb, cb, val_a);
+
+        let mut ret = vec![
+            Action {
+                ty: "set",
+                id: id_a.into(),
+                value: val_b.map(char::from),
+            },
+            Action {
+                ty: "set",
+                id: id_b.into(),
+                value: val_a.map(char::from),
+            },
+        ];
+        self.propagate(ra, ca, &mut ret);
+        self.propagate(rb, cb, &mut ret);
+        self.record_actions_from(&ret);
+        Ok(ret)
+    }
+
+    /// The DOM ids of the input cells, in the order a user should fill them in
+    ///
+    /// This is share order: the first row, starting with the header, then each
+    /// pair of data cells from top to bottom. It differs from reading order
+    /// because of the diagonal layout.
+    pub fn focus_order(&self) -> Vec<String> {
+        self.share_data_indices()
+            .map(|(ridx, cidx)| &self.rows[ridx].cells[cidx])
+            .filter(|cell| self.is_editable(cell))
+            .map(|cell| cell.dom_id.clone())
+            .collect()
+    }
+
+    /// The first blank input cell after the given cell, in `focus_order`
+    pub fn next_empty_cell(&self, ridx: usize, cidx: usize) -> Option<(usize, usize)> {
+        self.share_data_indices()
+            .skip_while(|&idx| idx != (ridx, cidx))
+            .skip(1)
+            .find(|&(r, c)| {
+                let cell = &self.rows[r].cells[c];
+                self.is_editable(cell) && cell.val.is_none()
+            })
+    }
+
+    /// The DOM ids of every filled-in cell whose value was (transitively) computed
+    /// from the given cell, in the order propagation reaches them
+    ///
+    /// Overridden cells, and anything reached only through them, are excluded since
+    /// their values don't depend on anything. Returns nothing for a nonexistent cell.
+    pub fn influence_path(&self, ridx: usize, cidx: usize) -> Vec<String> {
+        if self
+            .rows
+            .get(ridx)
+            .and_then(|row| row.cells.get(cidx))
+            .is_none()
+        {
+            return vec![];
+        }
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::from([(ridx, cidx)]);
+        let mut ret = vec![];
+        while let Some((r, c)) = queue.pop_front() {
+            for (dr, dc) in self.direct_dependents(r, c) {
+                let cell = &self.rows[dr].cells[dc];
+                if cell.overridden || cell.val.is_none() || !seen.insert((dr, dc)) {
+                    continue;
+                }
+                ret.push(cell.dom_id.clone());
+                queue.push_back((dr, dc));
+            }
+        }
+        ret
+    }
+
+    /// Looks up the (row, cell) indices of a cell by its DOM id
+    fn find_cell(&self, id: &str) -> Result<(usize, usize), Error> {
+        for (ridx, row) in self.rows.iter().enumerate() {
+            if let Some(cidx) = row.cells.iter().position(|cell| cell.dom_id == id) {
+                return Ok((ridx, cidx));
+            }
+        }
+        Err(Error::UnknownCell {
+            id: id.into(),
+            reason: "not in this worksheet",
+        })
+    }
+
+    /// The checksum residue of the HRP and all the share data entered up to and
+    /// including the given row, for a running "residue so far" display
+    ///
+    /// Returns `None` if the row is out of range or any of that data is missing.
+    /// Once all the share data is included, this is the residue of the whole share,
+    /// which for a valid share is the target residue.
+    pub fn partial_residue(&self, up_to_ridx: usize) -> Option<fe::Poly> {
+        if up_to_ridx >= self.rows.len() {
+            return None;
+        }
+        let data: Vec<Fe> = self
+            .share_data_indices()
+            .take_while(|&(ridx, _)| ridx <= up_to_ridx)
+            .map(|(ridx, cidx)| self.rows[ridx].cells[cidx].val)
+            .collect::<Option<_>>()?;
+        let mut engine = fe::StreamingChecksum::new(self.checksum);
+        for fe in fe::Poly::from_hrp_and_data(&self.hrp, data).iter() {
+            engine.push(fe);
+        }
+        Some(engine.current())
+    }
+
+    /// The share data, or `None` if any of it is missing
+    pub fn share_data_fes(&self) -> Option<Vec<Fe>> {
+        self.share_data().collect()
+    }
+
+    /// Whether the global residue row is complete and matches the target residue
+    ///
+    /// In create mode the global residue is fixed, so this reflects whether the
+    /// share data is complete and valid instead.
+    pub fn global_residue_status(&self) -> GlobalResidueStatus {
+        let residue: Option<Vec<Fe>> = match self.mode {
+            CreateMode::Create if !self.is_complete() => None,
+            CreateMode::Create => {
+                return if self.is_valid() {
+                    GlobalResidueStatus::Valid
+                } else {
+                    GlobalResidueStatus::Invalid
+                }
+            }
+            CreateMode::Verify | CreateMode::Blank => self
+                .rows
+                .last()
+                .unwrap()
+                .cells
+                .iter()
+                .map(|c| c.val)
+                .collect(),
+        };
+        match residue {
+            None => GlobalResidueStatus::Incomplete,
+            Some(residue) => {
+                if residue
+                    .into_iter()
+                    .eq(self.checksum.target_residue().iter())
+                {
+                    GlobalResidueStatus::Valid
+                } else {
+                    GlobalResidueStatus::Invalid
+                }
+            }
+        }
+    }
+
+    /// Finds the single incorrect character, if any, which would explain an invalid
+    /// checksum
+    ///
+    /// Returns the share data position and its corrected value, suitable for passing
+    /// to `apply_correction`. Returns `None` if the share is incomplete or valid, or
+    /// if more than one character is wrong.
+    pub fn find_single_error(&self) -> Option<(usize, Fe)> {
+        let data = self.share_data_fes()?;
+        let n_data = data.len();
+        let poly = fe::Poly::from_hrp_and_data(&self.hrp, data);
+        let n_hrp = poly.iter().count() - n_data;
+        let (pos, value) = poly.correct_single_error(self.checksum)?;
+        // An "error" in the HRP can't be corrected by editing the worksheet
+        pos.checked_sub(n_hrp).map(|pos| (pos, value))
+    }
+
+    /// Replaces the share data character at a position with a corrected value
+    ///
+    /// Returns the actions to update the worksheet, including a `flash_set` to draw
+    /// attention to the corrected cell.
+    pub fn apply_correction(&mut self, position: usize, value: Fe) -> Result<Vec<Action>, Error> {
+        let (ridx, cidx) =
+            self.position_to_cell(position)
+                .ok_or_else(|| Error::InvalidPosition {
+                    pos: position,
+                    n_positions: self.share_data_indices().count(),
+                })?;
+        self.check_input_cell(ridx, cidx)?;
+
+        self.write_input(ridx, cidx, Some(value));
+        let mut ret = vec![Action {
+            ty: "flash_set",
+            id: self.rows[ridx].cells[cidx].dom_id.clone(),
+            value: Some(value.into()),
+        }];
+        self.propagate(ridx, cidx, &mut ret);
+        self.record_actions_from(&ret);
+        Ok(ret)
+    }
+
+    /// Whether every share data cell has been filled in
+    pub fn is_complete(&self) -> bool {
+        self.share_data().all(|fe| fe.is_some())
+    }
+
+    /// Whether the share is complete and its checksum is valid
+    pub fn is_valid(&self) -> bool {
+        match self.share_data_fes() {
+            Some(data) => {
+                let poly = fe::Poly::from_hrp_and_data(&self.hrp, data);
+                self.checksum.bch_code().verify_checksum(&poly)
+            }
+            None => false,
+        }
+    }
+
+    /// The share data packed five bits per character into a `u64`, for fast
+    /// comparison and hashing
+    ///
+    /// Returns `None` if the share is incomplete or has more than 12 data characters.
+    /// Leading `Q`s do not affect the value, so only compare shares of the same size.
+    pub fn packed_data(&self) -> Option<u64> {
+        let data = self.share_data_fes()?;
+        if data.len() > 12 {
+            return None;
+        }
+        Some(
+            data.into_iter()
+                .fold(0, |acc, fe| (acc << 5) | u64::from(u8::from(fe))),
+        )
+    }
+
+    /// Explains why the checksum does not validate
+    ///
+    /// If the data would be valid under a different checksum, the user has probably
+    /// configured the wrong checksum rather than made a mistake entering the data.
+    pub fn diagnose_failure(&self) -> FailureKind {
+        let data = match self.share_data_fes() {
+            Some(data) => data,
+            None => return FailureKind::Incomplete,
+        };
+        let poly = fe::Poly::from_hrp_and_data(&self.hrp, data);
+        if self.checksum.bch_code().verify_checksum(&poly) {
+            return FailureKind::NoFailure;
+        }
+        [Checksum::Codex32, Checksum::Bech32, Checksum::Codex32Long]
+            .into_iter()
+            .filter(|&other| other != self.checksum)
+            .find(|other| other.bch_code().verify_checksum(&poly))
+            .map_or(FailureKind::DataError, FailureKind::WrongChecksumType)
+    }
+
+    /// Lists the DOM IDs of the share data cells of a complete share whose
+    /// characters are easily misread, so that the user can double-check them
+    pub fn suggest_review(&self) -> Vec<String> {
+        if !self.is_complete() {
+            return vec![];
+        }
+        self.share_data_indices()
+            .map(|(ridx, cidx)| &self.rows[ridx].cells[cidx])
+            .filter(|cell| {
+                cell.val
+                    .map(|fe| CONFUSABLE_CHARS.contains(char::from(fe)))
+                    .unwrap_or(false)
+            })
+            .map(|cell| cell.dom_id.clone())
+            .collect()
+    }
+
+    /// Summarizes the state of the share
+    pub fn summary(&self) -> ShareSummary {
+        let n_total = self.share_data().count();
+        let n_filled = self.share_data().filter(Option::is_some).count();
+        ShareSummary {
+            header: self.header_str(),
+            completion: n_filled as f64 / n_total as f64,
+            valid: self.is_valid(),
+            index: self.share_index().map(From::from),
+            mode: self.mode,
+        }
+    }
+
+    /// The threshold from the header, if it has been filled in with a digit
+    pub fn threshold(&self) -> Option<usize> {
+        let ch = char::from(self.share_data().next().flatten()?);
+        ch.to_digit(10).map(|d| d as usize)
+    }
+
+    /// The four-character identifier from the header, if it has been filled in
+    ///
+    /// Every share of a given secret has the same identifier.
+    pub fn identifier(&self) -> Option<String> {
+        self.share_data()
+            .skip(1)
+            .take(4)
+            .map(|fe| fe.map(char::from))
+            .collect()
+    }
+
+    /// The share index character from the header, if it has been filled in
+    pub fn share_index(&self) -> Option<Fe> {
+        self.share_data().nth(5).flatten()
+    }
+
+    /// The threshold digit, identifier and share index from the header, if it has
+    /// been completely filled in
+    pub fn parse_header(&self) -> Option<(char, String, char)> {
+        let threshold = char::from(self.share_data().next().flatten()?);
+        Some((threshold, self.identifier()?, self.share_index()?.into()))
+    }
+
+    /// Fills in the threshold digit, four-character identifier and share index of the
+    /// header in one go
+    ///
+    /// If any character is invalid, no cell is changed.
+    pub fn fill_header(
+        &mut self,
+        threshold: char,
+        identifier: &str,
+        index: char,
+    ) -> Result<Vec<Action>, Error> {
+        let len = identifier.chars().count();
+        if len != 4 {
+            return Err(Error::BadIdentifierLen { len });
+        }
+        let header: Vec<char> = iter::once(threshold)
+            .chain(identifier.chars())
+            .chain(iter::once(index))
+            .collect();
+        for &ch in &header {
+            Fe::try_from(ch.to_ascii_uppercase())?;
+        }
+
+        let vals: Vec<String> = header.iter().map(char::to_string).collect();
+        let changes: Vec<_> = self
+            .share_data_indices()
+            .zip(&vals)
+            .map(|((ridx, cidx), val)| (ridx, cidx, val.as_str()))
+            .collect();
+        if changes.len() < header.len() {
+            return Err(Error::InvalidPosition {
+                pos: header.len() - 1,
+                n_positions: changes.len(),
+            });
+        }
+        self.handle_input_batch(&changes)
+    }
+
+    /// Constructs a completely filled-in worksheet from a share string
+    ///
+    /// The worksheet is in verify mode, with every computed cell filled in. Errors
+    /// if the share's checksum is invalid. Whitespace and dashes used to group the
+    /// share's characters are ignored.
+    pub fn from_share_string(s: &str, checksum: Checksum, idx: usize) -> Result<Worksheet, Error> {
+        let s = checksum.normalize_share_string(s).to_ascii_uppercase();
+        let sep_char = checksum.separator_char();
+        let sep = s.rfind(sep_char).ok_or(Error::MissingSeparator)?;
+        let mut ret = Worksheet::new_padded(&s[..sep], s.len(), checksum, idx)?;
+        ret.set_mode(CreateMode::Verify);
+        for (pos, ch) in s[sep + sep_char.len_utf8()..].chars().enumerate() {
+            // In verify mode every share data cell is input, so this only fails on bad characters
+            Fe::try_from(ch)?;
+            ret.set_data_char(pos, ch)?;
+        }
+        if ret.is_valid() {
+            Ok(ret)
+        } else {
+            Err(Error::BadChecksum)
+        }
+    }
+
+    /// Constructs a completely filled-in worksheet from raw 5-bit share data values
+    ///
+    /// This is the binary counterpart of `from_share_string`, except that the
+    /// checksum is not required to be valid. The worksheet is in verify mode.
+    pub fn from_values(
+        hrp: &str,
+        values: &[u8],
+        checksum: Checksum,
+        idx: usize,
+    ) -> Result<Worksheet, Error> {
+        let fes = values
+            .iter()
+            .map(|&value| Fe::try_from(value))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut ret = Worksheet::new_padded(hrp, hrp.len() + 1 + fes.len(), checksum, idx)?;
+        ret.set_mode(CreateMode::Verify);
+        for (pos, fe) in fes.into_iter().enumerate() {
+            ret.set_data_char(pos, fe.into())?;
+        }
+        Ok(ret)
+    }
+
+    /// Outputs the complete share as a string, or `None` if any share data is missing
+    ///
+    /// The string is uppercase, as in the rendered worksheet.
+    pub fn to_share_string(&self) -> Option<String> {
+        let mut ret = String::with_capacity(self.size);
+        ret.push_str(&self.hrp);
+        ret.push(self.checksum.separator_char());
+        for fe in self.share_data() {
+            ret.push(fe?.into());
+        }
+        Some(ret)
+    }
+
+    /// Outputs the complete share as an uppercase string, as `to_share_string`
+    pub fn to_share_string_uppercase(&self) -> Option<String> {
+        self.to_share_string()
+    }
+
+    /// Outputs the complete share as a lowercase string, including the HRP
+    ///
+    /// This is the usual form for exporting a share.
+    pub fn to_share_string_lowercase(&self) -> Option<String> {
+        self.to_share_string().map(|s| s.to_ascii_lowercase())
+    }
+
     /// Dumps all the cell data into a giant string
+    ///
+    /// The cell values come first, one character per cell. If any cell has been
+    /// input or overridden, they are followed by a `:` and a comma-separated list of
+    /// `n=seq` entries giving the revision at which the `n`th cell was input, and
+    /// `n!` entries for overridden cells.
     pub fn cells_into_str(&self, s: &mut String) {
         for row in &self.rows {
             for cell in &row.cells {
                 s.push(cell.val.map(char::from).unwrap_or(' '));
             }
         }
+        let metadata: Vec<String> = self
+            .rows
+            .iter()
+            .flat_map(|row| row.cells.iter())
+            .enumerate()
+            .flat_map(|(n, cell)| {
+                let seq = Some(format!("{}={}", n, cell.seq)).filter(|_| cell.seq > 0);
+                let overridden = Some(format!("{}!", n)).filter(|_| cell.overridden);
+                seq.into_iter().chain(overridden)
+            })
+            .collect();
+        if !metadata.is_empty() {
+            s.push(':');
+            s.push_str(&metadata.join(","));
+        }
     }
 
     /// Reads all the cell data from a string output by `cells_into_str`
+    ///
+    /// If the worksheet needs reconstruction, its rows are rebuilt first.
     pub fn cells_from_str(&mut self, s: &str) -> Result<(), Error> {
+        self.reconstruct()?;
+        // Strings saved before revisions were stored have no metadata
+        let (s, metadata) = match s.split_once(':') {
+            Some((s, metadata)) => (s, metadata),
+            None => (s, ""),
+        };
         let mut ridx = 0;
         let mut cidx = 0;
         for ch in s.chars() {
@@ -562,6 +2181,8 @@ impl Worksheet {
                 ' ' => None,
                 x => Some(Fe::try_from(x)?),
             };
+            self.rows[ridx].cells[cidx].seq = 0;
+            self.rows[ridx].cells[cidx].overridden = false;
 
             cidx += 1;
             if cidx == self.rows[ridx].cells.len() {
@@ -573,11 +2194,41 @@ impl Worksheet {
             }
         }
 
-        if ridx == self.rows.len() && cidx == 0 {
-            Ok(())
-        } else {
-            Err(Error::BadShareDataLen { len: s.len() })
+        if ridx != self.rows.len() || cidx != 0 {
+            return Err(Error::BadStoredCellCount {
+                len: s.chars().count(),
+                expected: self.rows.iter().map(|row| row.cells.len()).sum(),
+            });
+        }
+
+        self.revision = 0;
+        for entry in metadata.split(',').filter(|entry| !entry.is_empty()) {
+            let bad = || Error::BadCellMetadata {
+                entry: entry.into(),
+            };
+            let (n, seq) = match entry.strip_suffix('!') {
+                Some(n) => (n, None),
+                None => {
+                    let (n, seq) = entry.split_once('=').ok_or_else(bad)?;
+                    (n, Some(seq.parse::<u64>().map_err(|_| bad())?))
+                }
+            };
+            let n: usize = n.parse().map_err(|_| bad())?;
+            let cell = self
+                .rows
+                .iter_mut()
+                .flat_map(|row| row.cells.iter_mut())
+                .nth(n)
+                .ok_or_else(bad)?;
+            match seq {
+                Some(seq) => {
+                    cell.seq = seq;
+                    self.revision = self.revision.max(seq);
+                }
+                None => cell.overridden = true,
+            }
         }
+        Ok(())
     }
 }
 
@@ -585,26 +2236,170 @@ impl Worksheet {
 mod tests {
     use super::*;
 
+    /// BIP-93 test vector share
+    const SHARE_2NAMEA: &str = "MS12NAMEA320ZYXWVUTSRQPNMLKJHGFEDCAXRPP870HKKQRM";
+
+    /// The values of every cell of a worksheet, ignoring when they were input
+    fn values(worksheet: &Worksheet) -> Vec<Option<Fe>> {
+        worksheet
+            .rows
+            .iter()
+            .flat_map(|row| row.cells.iter().map(|cell| cell.val))
+            .collect()
+    }
+
+    /// Fills in all the non-checksum characters of a worksheet from a share string
+    fn fill(worksheet: &mut Worksheet, share: &str) {
+        let data = &share[worksheet.hrp.len() + 1..];
+        let indices: Vec<_> = worksheet.share_data_indices().collect();
+        for ((ridx, cidx), ch) in indices.into_iter().zip(data.chars()) {
+            if !worksheet.rows[ridx].cells[cidx].is_checksum {
+                let ch = ch.to_string();
+                assert!(worksheet.handle_input_change(ridx, cidx, &ch).is_ok());
+            }
+        }
+    }
+
     #[test]
-    fn user_test() {
+    fn influence_path() {
         let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
-        assert!(worksheet.handle_input_change(0, 0, "c").is_ok());
-        assert!(worksheet.handle_input_change(0, 1, "c").is_ok());
-        assert!(worksheet.handle_input_change(0, 2, "c").is_ok());
-        assert!(worksheet.handle_input_change(0, 3, "c").is_ok());
-        assert!(worksheet.handle_input_change(0, 4, "c").is_ok());
-        assert!(worksheet.handle_input_change(0, 5, "c").is_ok());
-        assert!(worksheet.handle_input_change(0, 6, "c").is_ok());
-        assert!(worksheet.handle_input_change(0, 7, "c").is_ok());
-        assert!(worksheet.handle_input_change(0, 8, "c").is_ok());
-        assert!(worksheet.handle_input_change(0, 9, "c").is_ok());
-        assert!(worksheet.handle_input_change(0, 10, "c").is_ok());
-        assert!(worksheet.handle_input_change(0, 11, "c").is_ok());
-        assert!(worksheet.handle_input_change(0, 12, "c").is_ok());
+        worksheet.set_mode(CreateMode::Verify);
+        // A lone cell only reaches its sum with the HRP residue; the residue below
+        // that also needs the neighbouring sum
+        worksheet.handle_input_change(0, 0, "2").unwrap();
+        assert_eq!(worksheet.influence_path(0, 0), ["inp_0_2_0"]);
 
-        assert!(worksheet.handle_input_change(2, 13, "c").is_ok()); // move this berofe 1414
-        assert!(worksheet.handle_input_change(2, 14, "c").is_ok());
-        assert!(worksheet.handle_input_change(4, 13, "c").is_ok());
+        let worksheet = Worksheet::from_share_string(SHARE_2NAMEA, Checksum::Codex32, 0).unwrap();
+        let path = worksheet.influence_path(0, 0);
+        // The threshold digit goes into the first sum, whose residue fills the next row
+        assert_eq!(path[0], "inp_0_2_0");
+        let residue_row: Vec<_> = (0..13).map(|n| format!("inp_0_3_{}", n)).collect();
+        assert_eq!(path[1..14], residue_row[..]);
+        // ...and from there reaches the whole global residue
+        let last = worksheet.rows.len() - 1;
+        for cell in &worksheet.rows[last].cells {
+            assert!(path.contains(&cell.dom_id));
+        }
+        // The neighbouring sum does not depend on it
+        assert!(!path.contains(&"inp_0_2_1".to_string()));
+        // Paths never revisit a cell
+        let unique: HashSet<_> = path.iter().collect();
+        assert_eq!(unique.len(), path.len());
+
+        assert!(worksheet.influence_path(last, 0).is_empty());
+        assert!(worksheet.influence_path(last + 1, 0).is_empty());
+    }
+
+    #[test]
+    fn reset_computed() {
+        for mode in [CreateMode::Create, CreateMode::Verify] {
+            let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+            worksheet.set_mode(mode);
+            fill(&mut worksheet, SHARE_2NAMEA);
+            let filled = worksheet.clone();
+
+            let actions = worksheet.reset_computed();
+            assert!(!actions.is_empty());
+            for (ridx, row) in worksheet.rows.iter().enumerate() {
+                for (cidx, cell) in row.cells.iter().enumerate() {
+                    if worksheet.is_computed(ridx, cidx) {
+                        assert_eq!(cell.val, None);
+                        let id = cell.dom_id.as_str();
+                        let was_set = filled.rows[ridx].cells[cidx].val.is_some();
+                        assert_eq!(
+                            actions
+                                .iter()
+                                .any(|act| act.as_tuple() == ("set", id, None)),
+                            was_set,
+                        );
+                    } else {
+                        // User data, the HRP residue and the fixed global residue survive
+                        assert_eq!(cell.val, filled.rows[ridx].cells[cidx].val);
+                    }
+                }
+            }
+            assert!(worksheet.reset_computed().is_empty());
+
+            worksheet.recompute_all();
+            assert_eq!(values(&worksheet), values(&filled));
+        }
+    }
+
+    #[test]
+    fn clear_input() {
+        for mode in [CreateMode::Create, CreateMode::Verify] {
+            let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+            worksheet.set_mode(mode);
+            fill(&mut worksheet, SHARE_2NAMEA);
+            let mut expected = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+            expected.set_mode(mode);
+            fill(&mut expected, SHARE_2NAMEA);
+            expected.write_input(0, 3, None);
+            expected.recompute_all();
+
+            // Clearing a data cell blanks everything computed from it
+            let dependents = worksheet.influence_path(0, 3);
+            assert!(!dependents.is_empty());
+            let actions = worksheet.handle_input_change(0, 3, "").unwrap();
+            assert_eq!(values(&worksheet), values(&expected));
+            for id in &dependents {
+                assert!(actions
+                    .iter()
+                    .any(|act| act.as_tuple() == ("set", id.as_str(), None)));
+            }
+            assert!(worksheet.influence_path(0, 3).is_empty());
+
+            // ...and re-entering it restores them
+            worksheet.handle_input_change(0, 3, "M").unwrap();
+            assert_eq!(worksheet.influence_path(0, 3), dependents);
+        }
+    }
+
+    #[test]
+    fn user_test() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        assert!(worksheet.handle_input_change(0, 0, "c").is_ok());
+        assert!(worksheet.handle_input_change(0, 1, "c").is_ok());
+        assert!(worksheet.handle_input_change(0, 2, "c").is_ok());
+        assert!(worksheet.handle_input_change(0, 3, "c").is_ok());
+        assert!(worksheet.handle_input_change(0, 4, "c").is_ok());
+        assert!(worksheet.handle_input_change(0, 5, "c").is_ok());
+        assert!(worksheet.handle_input_change(0, 6, "c").is_ok());
+        assert!(worksheet.handle_input_change(0, 7, "c").is_ok());
+        assert!(worksheet.handle_input_change(0, 8, "c").is_ok());
+        assert!(worksheet.handle_input_change(0, 9, "c").is_ok());
+        assert!(worksheet.handle_input_change(0, 10, "c").is_ok());
+        assert!(worksheet.handle_input_change(0, 11, "c").is_ok());
+        assert!(worksheet.handle_input_change(0, 12, "c").is_ok());
+
+        let actions = worksheet.handle_input_change(2, 13, "c").unwrap(); // move this berofe 1414
+        assert_eq!(actions.len(), 21);
+        assert_eq!(
+            actions[0].as_tuple(),
+            ("flash_set", "inp_0_2_13", Some('C'))
+        );
+        assert_eq!(actions[1].as_tuple(), ("set", "inp_0_4_11", Some('W')));
+        assert_eq!(actions[7].as_tuple(), ("set", "inp_0_15_0", Some('8')));
+        assert_eq!(actions[19].as_tuple(), ("set", "inp_0_15_12", Some('K')));
+        assert_eq!(actions[20].as_tuple(), ("focus", "inp_0_2_14", None));
+
+        let actions = worksheet.handle_input_change(2, 14, "c").unwrap();
+        let expected = [
+            Action::new("flash_set", "inp_0_2_14", Some('C')),
+            Action::new("set", "inp_0_4_12", Some('N')),
+            Action::new("set", "inp_0_6_10", Some('U')),
+            Action::new("set", "inp_0_8_8", Some('6')),
+            Action::new("set", "inp_0_10_6", Some('3')),
+            Action::new("set", "inp_0_12_4", Some('K')),
+            Action::new("set", "inp_0_14_2", Some('T')),
+            Action::new("set", "inp_0_16_0", Some('V')),
+            Action::new("focus", "inp_0_4_13", None),
+        ];
+        assert_eq!(
+            actions.iter().map(Action::as_tuple).collect::<Vec<_>>(),
+            expected.iter().map(Action::as_tuple).collect::<Vec<_>>(),
+        );
+        assert!(worksheet.handle_input_change(4, 13, "c").is_ok());
         assert!(worksheet.handle_input_change(4, 14, "c").is_ok());
         assert!(worksheet.handle_input_change(6, 13, "c").is_ok());
         assert!(worksheet.handle_input_change(6, 14, "c").is_ok());
@@ -639,37 +2434,1082 @@ mod tests {
         assert_eq!(worksheet.cell_below(2, 15), Some((3, 13)));
         assert_eq!(worksheet.cell_below(3, 13), Some((4, 13)));
 
-        assert!(worksheet.handle_input_change(0, 5, "").is_ok());
+        let actions = worksheet.handle_input_change(0, 5, "").unwrap();
+        assert_eq!(actions[0].as_tuple(), ("set", "inp_0_2_5", None));
         assert_eq!(worksheet.rows[20].cells[14].val, None);
         assert_eq!(worksheet.rows[22].cells[13].val, None);
 
         let mut s = String::new();
         worksheet.cells_into_str(&mut s);
         worksheet.cells_from_str(&s).unwrap();
+
+        let n_cells = worksheet.rows.iter().map(|row| row.cells.len()).sum();
+        let cells = &s[..s.find(':').unwrap_or(s.len())];
+        assert_eq!(
+            worksheet.cells_from_str(&cells[1..]),
+            Err(Error::BadStoredCellCount {
+                len: n_cells - 1,
+                expected: n_cells,
+            }),
+        );
+    }
+
+    #[test]
+    fn validate_structure() {
+        for checksum in [Checksum::Codex32, Checksum::Bech32, Checksum::Codex32Long] {
+            for size in 0..130 {
+                match Worksheet::new("ms", size, checksum, 0) {
+                    Ok(worksheet) => worksheet.validate_structure().unwrap(),
+                    Err(Error::TooShort { .. })
+                    | Err(Error::TooLong { .. })
+                    | Err(Error::OddLength { .. }) => {}
+                    Err(e) => panic!("size {}: {}", size, e),
+                }
+            }
+        }
+
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        worksheet.rows[3].cells.pop();
+        assert!(worksheet.validate_structure().is_err());
+    }
+
+    #[test]
+    fn verify_mode() {
+        let mut create = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        fill(&mut create, SHARE_2NAMEA);
+
+        let mut verify = create.clone();
+        verify.set_mode(CreateMode::Verify);
+        assert_eq!(verify.to_share_string().as_deref(), Some(SHARE_2NAMEA));
+        assert_eq!(verify.rows[1], create.rows[1]);
+        assert_eq!(verify.rows[2].cells[0].val, None);
+        assert_eq!(verify.rows.last().unwrap().cells[0].val, None);
+
+        let actions = verify.recompute_all();
+        assert!(!actions.is_empty());
+        assert_eq!(verify.rows, create.rows);
+        // Recomputing again changes nothing
+        assert!(verify.recompute_all().is_empty());
+
+        // Errors now show up in the global residue
+        let original = char::from(create.rows[0].cells[6].val.unwrap()).to_string();
+        assert!(verify.handle_input_change(0, 6, "q").is_ok());
+        assert_ne!(verify.rows.last(), create.rows.last());
+        assert!(!verify.is_valid());
+        assert!(verify.handle_input_change(0, 6, &original).is_ok());
+        assert_eq!(verify.rows, create.rows);
+
+        // ...and switching back to create mode recomputes the checksum
+        assert!(verify.handle_input_change(0, 6, "q").is_ok());
+        verify.set_mode(CreateMode::Create);
+        verify.recompute_all();
+        assert!(verify.is_valid());
+    }
+
+    #[test]
+    fn suggest_review() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        assert!(worksheet.handle_input_change(0, 0, "2").is_ok());
+        // Incomplete shares are not linted
+        assert!(worksheet.suggest_review().is_empty());
+
+        fill(&mut worksheet, SHARE_2NAMEA);
+        let flagged = worksheet.suggest_review();
+        let expected: Vec<_> = worksheet
+            .share_data_indices()
+            .zip(SHARE_2NAMEA[3..].chars())
+            .filter(|(_, ch)| "0DQ2Z5S6GUV".contains(*ch))
+            .map(|((ridx, cidx), _)| worksheet.rows[ridx].cells[cidx].dom_id.clone())
+            .collect();
+        assert_eq!(flagged, expected);
+        // 2, 2, 0, Z, V, U, S, Q, G, D, 0, Q
+        assert_eq!(flagged.len(), 12);
+        assert_eq!(flagged[0], "inp_0_0_0");
+    }
+
+    #[test]
+    fn too_short() {
+        // Exactly the HRP and checksum, with no room for the separator
+        assert_eq!(
+            Worksheet::new("ms", 15, Checksum::Codex32, 0),
+            Err(Error::TooShort {
+                minimum: 16,
+                actual: 15
+            }),
+        );
+        assert_eq!(
+            Worksheet::new_padded("ms", 8, Checksum::Bech32, 0),
+            Err(Error::TooShort {
+                minimum: 9,
+                actual: 8
+            }),
+        );
+        // Room for only the HRP, separator and checksum
+        let worksheet = Worksheet::new("ms", 16, Checksum::Codex32, 0).unwrap();
+        assert_eq!(worksheet.rows.len(), 3);
+    }
+
+    #[test]
+    fn empty_hrp() {
+        for checksum in [Checksum::Codex32, Checksum::Bech32, Checksum::Codex32Long] {
+            assert_eq!(
+                Worksheet::new("", 100, checksum, 0).err(),
+                Some(Error::EmptyHrp),
+            );
+            assert_eq!(
+                Worksheet::new_padded("", 101, checksum, 0).err(),
+                Some(Error::EmptyHrp),
+            );
+        }
+    }
+
+    #[test]
+    fn long_codex32() {
+        // BIP-93 test vector 5
+        let share = "MS100C8VSM32ZXFGUHPCHTLUPZRY9X8GF2TVDW0S3JN54KHCE6MUA7LQPZYGSFJD6AN074RXVCEMLH8WU3TK925ACDEFGHJKLMNPQRSTUVWXY06FHPV80UNDVARHRAK";
+        // The limits apply to the data part, after the HRP and separator
+        Worksheet::new("ms", 96, Checksum::Codex32, 0).unwrap();
+        assert_eq!(
+            Worksheet::new_padded("ms", 97, Checksum::Codex32, 0),
+            Err(Error::TooLong {
+                maximum: 96,
+                actual: 97
+            }),
+        );
+        Worksheet::new_padded("bitcoin", 101, Checksum::Codex32, 0).unwrap();
+        assert_eq!(
+            Worksheet::new_padded("ms", 97, Checksum::Codex32Long, 0),
+            Err(Error::TooShort {
+                minimum: 99,
+                actual: 97
+            }),
+        );
+        Worksheet::new("ms", 100, Checksum::Codex32Long, 0).unwrap();
+        assert_eq!(
+            Worksheet::new("ms", 132, Checksum::Codex32Long, 0),
+            Err(Error::TooLong {
+                maximum: 130,
+                actual: 132
+            }),
+        );
+
+        let mut worksheet = Worksheet::new_padded("ms", 127, Checksum::Codex32Long, 0).unwrap();
+        fill(&mut worksheet, share);
+        assert_eq!(worksheet.to_share_string().as_deref(), Some(share));
+    }
+
+    #[test]
+    fn dom_cells_json() {
+        let worksheet = Worksheet::new("ms", 17, Checksum::Bech32, 0).unwrap();
+        let json = worksheet.dom_cells_json(false).unwrap();
+        let cells: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+        // 3 HRP/separator cells, one symbol per row after the first, and
+        // 6 + 6 + 4 * (6 + 2 + 6) + 6 worksheet cells
+        assert_eq!(cells.len(), 3 + 10 + 74);
+        assert_eq!(cells.len(), worksheet.get_dom_cells(false).unwrap().len());
+
+        assert_eq!(cells[0]["ty"], "fixed_hrp");
+        assert_eq!(cells[0]["dom_id"], "cell_hrp_0");
+        assert_eq!(cells[0]["val"], "M");
+        assert_eq!(cells[0]["x"], 0);
+        assert_eq!(cells[0]["y"], 0);
+        assert_eq!(cells[0]["editable"], false);
+
+        assert_eq!(cells[3]["ty"], "share_data");
+        assert_eq!(cells[3]["dom_id"], "inp_0_0_0");
+        assert_eq!(cells[3]["val"], serde_json::Value::Null);
+        assert_eq!(cells[3]["x"], 3);
+        assert_eq!(cells[3]["editable"], true);
+    }
+
+    #[test]
+    fn to_csv() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        fill(&mut worksheet, SHARE_2NAMEA);
+        let csv = worksheet.to_csv();
+        let lines: Vec<_> = csv.lines().collect();
+        let n_cells: usize = worksheet.rows.iter().map(|row| row.cells.len()).sum();
+        assert_eq!(lines.len(), 1 + n_cells);
+        assert_eq!(lines[0], "row,col,type,value");
+        assert_eq!(lines[1], "0,0,share_data,2");
+        assert_eq!(lines[2], "0,1,share_data,N");
+
+        // Lines are in row order, so the HRP residue row follows the share data row
+        let n_row0 = worksheet.rows[0].cells.len();
+        let hrp_residue = format!("1,0,residue,{}", worksheet.rows[1].cells[0].val.unwrap());
+        assert_eq!(lines[1 + n_row0], hrp_residue);
+        let sum = format!("2,1,sum,{}", worksheet.rows[2].cells[1].val.unwrap());
+        assert!(lines.contains(&sum.as_str()));
+        assert!(lines.last().unwrap().starts_with("34,12,global_residue,"));
+        // Every line has exactly four fields, and the checksum is computed
+        for line in &lines[1..] {
+            assert_eq!(line.split(',').count(), 4);
+        }
+        assert!(lines
+            .iter()
+            .any(|line| line.contains("share_data_checksum,")));
+
+        // A blank worksheet has blank values
+        let blank = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        assert!(blank.to_csv().lines().any(|line| line == "0,0,share_data,"));
+    }
+
+    #[test]
+    fn not_editable() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        fill(&mut worksheet, SHARE_2NAMEA);
+        let before = worksheet.clone();
+
+        // HRP residue, sum, residue and global residue cells
+        for &(ridx, cidx) in &[(1, 0), (2, 0), (3, 0), (34, 0)] {
+            assert_eq!(
+                worksheet.handle_input_change(ridx, cidx, "q").err(),
+                Some(Error::NotEditable {
+                    row: ridx,
+                    cell: cidx,
+                }),
+            );
+        }
+        // Checksum characters are computed in create mode...
+        assert!(worksheet.handle_input_change(32, 13, "q").is_err());
+        assert_eq!(worksheet, before);
+        // ...but not in verify mode
+        worksheet.set_mode(CreateMode::Verify);
+        assert!(worksheet.handle_input_change(32, 13, "q").is_ok());
+    }
+
+    #[test]
+    fn swap_cells() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        worksheet.set_mode(CreateMode::Verify);
+        // Transpose the characters at positions 6 and 7 ("3" and "2")
+        let mut transposed = SHARE_2NAMEA.to_string();
+        transposed.replace_range(9..11, "23");
+        for (pos, ch) in transposed[3..].chars().enumerate() {
+            assert!(worksheet.set_data_char(pos, ch).is_ok());
+        }
+        assert!(!worksheet.is_valid());
+
+        let id_a = worksheet.rows[0].cells[6].dom_id.clone();
+        let id_b = worksheet.rows[0].cells[7].dom_id.clone();
+        let actions = worksheet.swap_cells(&id_a, &id_b).unwrap();
+        assert_eq!(actions[0].as_tuple(), ("set", &*id_a, Some('3')));
+        assert_eq!(actions[1].as_tuple(), ("set", &*id_b, Some('2')));
+        assert!(worksheet.is_valid());
+        assert_eq!(worksheet.to_share_string().as_deref(), Some(SHARE_2NAMEA));
+
+        // Computed cells can't be swapped
+        let residue = worksheet.rows[1].cells[0].dom_id.clone();
+        assert!(worksheet.swap_cells(&id_a, &residue).is_err());
+        assert!(worksheet.swap_cells(&id_a, "inp_0_99_0").is_err());
+    }
+
+    #[test]
+    fn from_share_string() {
+        let worksheet = Worksheet::from_share_string(SHARE_2NAMEA, Checksum::Codex32, 0).unwrap();
+        assert_eq!(worksheet.mode(), CreateMode::Verify);
+        assert_eq!(worksheet.to_share_string().as_deref(), Some(SHARE_2NAMEA));
+        let lower = SHARE_2NAMEA.to_ascii_lowercase();
+        assert_eq!(
+            Worksheet::from_share_string(&lower, Checksum::Codex32, 0),
+            Ok(worksheet.clone()),
+        );
+        let grouped = "ms1 2NAM-EA32 0ZYX-WVUT\nSRQP-NMLK-JHGF-EDCA-XRPP-870H-KKQRM";
+        assert_eq!(
+            Worksheet::from_share_string(grouped, Checksum::Codex32, 0),
+            Ok(worksheet),
+        );
+
+        let mut bad = SHARE_2NAMEA.to_string();
+        bad.replace_range(9..10, "Q");
+        assert_eq!(
+            Worksheet::from_share_string(&bad, Checksum::Codex32, 0).err(),
+            Some(Error::BadChecksum),
+        );
+        bad.replace_range(9..10, "B");
+        assert_eq!(
+            Worksheet::from_share_string(&bad, Checksum::Codex32, 0).err(),
+            Some(Error::BadBech32Char { ch: 'B' }),
+        );
+        assert_eq!(
+            Worksheet::from_share_string("MSQQQQ", Checksum::Codex32, 0).err(),
+            Some(Error::MissingSeparator),
+        );
+    }
+
+    #[test]
+    fn focus_order() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        let order = worksheet.focus_order();
+        let header: Vec<_> = (0..6).map(|n| format!("inp_0_0_{}", n)).collect();
+        assert_eq!(order[..6], header[..]);
+        assert_eq!(order[13..15], ["inp_0_2_13", "inp_0_2_14"]);
+        // Checksum cells are computed in create mode, so are skipped
+        assert_eq!(order.len(), 45 - 13);
+        assert_eq!(order.last().unwrap(), "inp_0_20_13");
+
+        worksheet.set_mode(CreateMode::Verify);
+        let order = worksheet.focus_order();
+        assert_eq!(order.len(), 45);
+        assert_eq!(order.last().unwrap(), "inp_0_32_14");
+    }
+
+    #[test]
+    fn with_index() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        fill(&mut worksheet, SHARE_2NAMEA);
+
+        let cloned = worksheet.with_index(7);
+        assert_eq!(cloned.to_share_string(), worksheet.to_share_string());
+        assert_eq!(cloned.with_index(0), worksheet);
+        for (ridx, row) in cloned.rows.iter().enumerate() {
+            for (cidx, cell) in row.cells.iter().enumerate() {
+                assert_eq!(crate::cell_from_name(&cell.dom_id), Ok([7, ridx, cidx]));
+            }
+        }
+    }
+
+    #[test]
+    fn global_residue_status() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        assert_eq!(
+            worksheet.global_residue_status(),
+            GlobalResidueStatus::Incomplete
+        );
+        worksheet.set_mode(CreateMode::Verify);
+        assert_eq!(
+            worksheet.global_residue_status(),
+            GlobalResidueStatus::Incomplete
+        );
+
+        for (pos, ch) in SHARE_2NAMEA[3..].chars().enumerate() {
+            assert_eq!(
+                worksheet.global_residue_status(),
+                GlobalResidueStatus::Incomplete
+            );
+            assert!(worksheet.set_data_char(pos, ch).is_ok());
+        }
+        assert_eq!(
+            worksheet.global_residue_status(),
+            GlobalResidueStatus::Valid
+        );
+        assert!(worksheet.set_data_char(44, 'Q').is_ok());
+        assert_eq!(
+            worksheet.global_residue_status(),
+            GlobalResidueStatus::Invalid
+        );
+
+        // In create mode the checksum is always recomputed to be valid
+        worksheet.set_mode(CreateMode::Create);
+        assert_eq!(
+            worksheet.global_residue_status(),
+            GlobalResidueStatus::Incomplete
+        );
+        worksheet.recompute_all();
+        assert_eq!(
+            worksheet.global_residue_status(),
+            GlobalResidueStatus::Valid
+        );
+    }
+
+    #[test]
+    fn revision() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        assert_eq!(worksheet.revision(), 0);
+
+        assert!(worksheet.handle_input_change(0, 0, "2").is_ok());
+        assert!(worksheet.handle_input_change(0, 1, "N").is_ok());
+        assert_eq!(worksheet.rows[0].cells[0].seq, 1);
+        assert_eq!(worksheet.rows[0].cells[1].seq, 2);
+        assert_eq!(worksheet.revision(), 2);
+
+        // Re-editing a cell (even blanking it) bumps it past the others
+        assert!(worksheet.handle_input_change(0, 0, "").is_ok());
+        assert_eq!(worksheet.rows[0].cells[0].seq, 3);
+        assert_eq!(worksheet.revision(), 3);
+        // Computed cells are not stamped
+        assert_eq!(worksheet.rows[2].cells[1].seq, 0);
+        // Rejected input is not a write
+        assert!(worksheet.handle_input_change(1, 0, "Q").is_err());
+        assert_eq!(worksheet.revision(), 3);
+
+        // Revisions survive a round trip through storage
+        let mut s = String::new();
+        worksheet.cells_into_str(&mut s);
+        let mut restored = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        restored.cells_from_str(&s).unwrap();
+        assert_eq!(restored.rows[0].cells[1].seq, 2);
+        assert_eq!(restored.revision(), 3);
+        assert_eq!(restored, worksheet);
+        assert!(restored.handle_input_change(0, 2, "a").is_ok());
+        assert_eq!(restored.rows[0].cells[2].seq, 4);
+
+        assert_eq!(
+            restored.cells_from_str(&format!("{}:1=x", &s[..s.find(':').unwrap()])),
+            Err(Error::BadCellMetadata {
+                entry: "1=x".into()
+            })
+        );
+    }
+
+    #[test]
+    fn expected_value() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        assert_eq!(worksheet.expected_value(2, 0), None);
+        assert!(worksheet.handle_input_change(0, 0, "2").is_ok());
+        // The first sum cell is the data cell plus the HRP residue
+        let hrp_residue = worksheet.rows[1].cells[0].val.unwrap();
+        assert_eq!(
+            worksheet.expected_value(2, 0),
+            Some(Fe::try_from('2').unwrap() + hrp_residue),
+        );
+        assert_eq!(worksheet.expected_value(3, 0), None);
+        assert!(worksheet.handle_input_change(0, 1, "N").is_ok());
+
+        // The residue of the first data pair is known, and matches what the sheet computed
+        let filled = worksheet.clone();
+        for cidx in 0..13 {
+            let expected = worksheet.expected_value(3, cidx);
+            assert!(expected.is_some());
+            assert_eq!(expected, filled.rows[3].cells[cidx].val);
+        }
+        // Even if the computed cells have been blanked
+        worksheet.clear_computed();
+        assert_eq!(worksheet.expected_value(3, 4), filled.rows[3].cells[4].val);
+        assert_eq!(worksheet.rows[3].cells[4].val, None);
+
+        // Input cells and bad indices have no expected value
+        assert_eq!(worksheet.expected_value(0, 0), None);
+        assert_eq!(worksheet.expected_value(0, 99), None);
+        assert_eq!(worksheet.expected_value(99, 0), None);
+    }
+
+    #[test]
+    fn handle_input_batch() {
+        let mut single = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        let mut batch = single.clone();
+        let changes = [
+            (0, 0, "2"),
+            (0, 1, "n"),
+            (0, 2, "A"),
+            (2, 13, "3"),
+            (0, 1, "N"),
+        ];
+        for &(ridx, cidx, val) in &changes {
+            assert!(single.handle_input_change(ridx, cidx, val).is_ok());
+        }
+        let actions = batch.handle_input_batch(&changes).unwrap();
+        assert_eq!(values(&batch), values(&single));
+
+        // Each cell is set at most once
+        let mut ids: Vec<_> = actions
+            .iter()
+            .filter(|a| a.ty == "set")
+            .map(|a| &a.id)
+            .collect();
+        let n_sets = ids.len();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), n_sets);
+        assert!(actions
+            .iter()
+            .any(|a| a.as_tuple() == ("flash_set", "inp_0_0_1", Some('N'))));
+
+        // A batch with a bad cell changes nothing
+        let before = batch.clone();
+        assert!(batch
+            .handle_input_batch(&[(0, 3, "m"), (1, 0, "q")])
+            .is_err());
+        assert_eq!(batch, before);
     }
 
     #[test]
-    fn minimal_bech32() {
+    fn dom_cell_kinds() {
+        let mut worksheet = Worksheet::new_padded("bc", 42, Checksum::Bech32, 0).unwrap();
+        let mut seen = HashSet::new();
+        for mode in [CreateMode::Create, CreateMode::Verify] {
+            worksheet.set_mode(mode);
+            for cell in worksheet.dom_cell_data(false) {
+                assert!(DomCellKind::ALL.contains(&cell.ty));
+                seen.insert(cell.ty);
+            }
+        }
+        // ...and every kind is actually used
+        assert_eq!(seen.len(), DomCellKind::ALL.len());
+
+        for kind in DomCellKind::ALL {
+            let json = serde_json::to_string(&kind).unwrap();
+            assert_eq!(json, format!("\"{}\"", kind.as_str()));
+        }
+    }
+
+    #[test]
+    fn check_consistency() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        assert!(worksheet.handle_input_change(0, 0, "2").is_ok());
+        assert!(worksheet.handle_input_change(0, 1, "N").is_ok());
+        assert!(worksheet.check_consistency().is_empty());
+
+        // Override a residue cell with the wrong value
+        let wrong = worksheet.rows[3].cells[4].val.unwrap() + Fe::one();
+        worksheet.rows[3].cells[4].val = Some(wrong);
+        assert_eq!(worksheet.check_consistency(), ["inp_0_3_4"]);
+
+        // A filled cell whose value can't be determined yet is not flagged
+        worksheet.rows[5].cells[0].val = Some(Fe::zero());
+        assert_eq!(worksheet.check_consistency(), ["inp_0_3_4"]);
+    }
+
+    #[test]
+    fn blank_mode() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        worksheet.set_mode(CreateMode::Blank);
+        let last = worksheet.rows.len() - 1;
+        for ridx in [1, last] {
+            assert!(worksheet.rows[ridx].cells.iter().all(|c| c.val.is_none()));
+        }
+
+        // Entering share data, checksum included, computes nothing
+        let data = &SHARE_2NAMEA[3..];
+        let indices: Vec<_> = worksheet.share_data_indices().collect();
+        for ((ridx, cidx), ch) in indices.into_iter().zip(data.chars()) {
+            let actions = worksheet
+                .handle_input_change(ridx, cidx, &ch.to_string())
+                .unwrap();
+            assert!(actions.iter().all(|act| act.ty == "focus"));
+        }
+        assert!(worksheet.rows[1].cells.iter().all(|c| c.val.is_none()));
+        assert_eq!(
+            worksheet.global_residue_status(),
+            GlobalResidueStatus::Incomplete
+        );
+
+        // A wrong residue is flagged but kept
+        let right = worksheet.expected_value(1, 0).unwrap();
+        let wrong = char::from(right + Fe::one()).to_string();
+        let actions = worksheet.handle_input_change(1, 0, &wrong).unwrap();
+        assert_eq!(
+            actions.last().unwrap().as_tuple(),
+            ("flash_error", "inp_0_1_0", None)
+        );
+        assert_eq!(worksheet.rows[1].cells[0].val, Some(right + Fe::one()));
+        assert_eq!(worksheet.check_consistency(), ["inp_0_1_0"]);
+
+        // Every residue and sum can be completed by hand
+        let expected = worksheet.recomputed();
+        for ridx in 1..worksheet.rows.len() {
+            for cidx in 0..worksheet.rows[ridx].cells.len() {
+                if worksheet.is_derived(ridx, cidx) {
+                    let ch = char::from(expected.rows[ridx].cells[cidx].val.unwrap());
+                    let actions = worksheet
+                        .handle_input_change(ridx, cidx, &ch.to_string())
+                        .unwrap();
+                    assert!(actions.iter().all(|act| act.ty != "flash_error"));
+                }
+            }
+        }
+        assert!(worksheet.check_consistency().is_empty());
+        assert_eq!(
+            worksheet.global_residue_status(),
+            GlobalResidueStatus::Valid
+        );
+
+        // Hand-entered cells survive a round trip through storage
+        let mut s = String::new();
+        worksheet.cells_into_str(&mut s);
+        let mut restored = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        restored.set_mode(CreateMode::Blank);
+        restored.cells_from_str(&s).unwrap();
+        assert_eq!(values(&restored), values(&worksheet));
+    }
+
+    #[test]
+    fn partial_residue() {
+        let worksheet = Worksheet::from_share_string(SHARE_2NAMEA, Checksum::Codex32, 0).unwrap();
+        let last = worksheet.rows.len() - 1;
+        assert_eq!(
+            worksheet.partial_residue(last),
+            Some(Checksum::Codex32.target_residue())
+        );
+        assert_eq!(worksheet.partial_residue(last + 1), None);
+
+        // The first row covers the data in it, and the rest only once it is entered
+        let first: Vec<Fe> = worksheet.share_data().take(13).flatten().collect();
+        let expected = fe::Poly::from_hrp_and_data("ms", first).checksum_polymod(Checksum::Codex32);
+        assert_eq!(worksheet.partial_residue(0), Some(expected));
+        let mut partial = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        fill(&mut partial, &SHARE_2NAMEA[..20]);
+        assert!(partial.partial_residue(4).is_some());
+        assert_eq!(partial.partial_residue(last), None);
+    }
+
+    #[test]
+    fn render_hints() {
+        let worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        let hints = worksheet.render_hints();
+        assert_eq!(hints.len(), worksheet.rows.len());
+        assert_eq!(
+            hints[1..5],
+            [
+                "HRP residue: the residue of the HRP \"MS\" under the Codex32 checksum",
+                "sum 1: the share data plus the HRP residue, followed by the next two share \
+                 characters",
+                "residue 1: the reduction of the first two characters of sum 1 by the Codex32 \
+                 checksum",
+                "sum 2: residue 1 plus sum 1 shifted left two places, followed by the next two \
+                 share characters",
+            ]
+        );
+        assert_eq!(
+            hints.last().unwrap(),
+            "global residue: residue 16 plus sum 16 shifted left two places, which must equal \
+             SECRETSHARE32"
+        );
+    }
+
+    #[test]
+    fn detect_misaligned_entry() {
+        let mut worksheet =
+            Worksheet::from_share_string(SHARE_2NAMEA, Checksum::Codex32, 0).unwrap();
+        assert!(worksheet.detect_misaligned_entry().is_empty());
+
+        // Enter the pairs of rows 6 and 8 into each other's rows
+        let k = worksheet.checksum.len();
+        let upper: Vec<_> = worksheet.rows[6].cells[k..].iter().map(|c| c.val).collect();
+        let lower: Vec<_> = worksheet.rows[8].cells[k..].iter().map(|c| c.val).collect();
+        for (cidx, (up, low)) in (k..).zip(upper.into_iter().zip(lower)) {
+            let up = char::from(up.unwrap()).to_string();
+            let low = char::from(low.unwrap()).to_string();
+            worksheet.handle_input_change(6, cidx, &low).unwrap();
+            worksheet.handle_input_change(8, cidx, &up).unwrap();
+        }
+        assert_eq!(
+            worksheet.global_residue_status(),
+            GlobalResidueStatus::Invalid
+        );
+        let ids: Vec<_> = [(6, k), (6, k + 1), (8, k), (8, k + 1)]
+            .iter()
+            .map(|&(ridx, cidx)| worksheet.rows[ridx].cells[cidx].dom_id.clone())
+            .collect();
+        assert_eq!(worksheet.detect_misaligned_entry(), ids);
+    }
+
+    #[test]
+    fn apply_correction() {
+        let mut worksheet =
+            Worksheet::from_share_string(SHARE_2NAMEA, Checksum::Codex32, 0).unwrap();
+        assert_eq!(worksheet.find_single_error(), None);
+        assert!(worksheet.set_data_char(20, 'Q').is_ok());
+        assert!(!worksheet.is_valid());
+
+        let (pos, value) = worksheet.find_single_error().unwrap();
+        assert_eq!(pos, 20);
+        assert_eq!(char::from(value), SHARE_2NAMEA.chars().nth(23).unwrap());
+        let actions = worksheet.apply_correction(pos, value).unwrap();
+        assert_eq!(actions[0].ty, "flash_set");
+        assert!(worksheet.is_valid());
+        assert_eq!(
+            worksheet.global_residue_status(),
+            GlobalResidueStatus::Valid
+        );
+
+        assert!(worksheet.apply_correction(45, value).is_err());
+    }
+
+    #[test]
+    fn cell_description() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        assert_eq!(worksheet.cell_description(1, 3), "HRP residue, column 3");
+        assert_eq!(worksheet.cell_description(4, 0), "sum 2, column 0");
+        assert_eq!(worksheet.cell_description(5, 7), "residue 2, column 7");
+        assert_eq!(
+            worksheet.cell_description(34, 1),
+            "global residue, column 1"
+        );
+
+        let err = worksheet.handle_input_change(0, 20, "Q").err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Invalid cell share data, column 20 (row 0 has 13 cells)",
+        );
+        let err = worksheet.handle_input_change(40, 0, "Q").err().unwrap();
+        assert!(err.to_string().contains("past the global residue"));
+    }
+
+    #[test]
+    fn set_override() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        fill(&mut worksheet, SHARE_2NAMEA);
+        let original = values(&worksheet);
+        let id = worksheet.rows[3].cells[4].dom_id.clone();
+        let computed = worksheet.rows[3].cells[4].val.unwrap();
+        let other = if char::from(computed) == 'Q' {
+            'P'
+        } else {
+            'Q'
+        };
+
+        let actions = worksheet.set_override(&id, other).unwrap();
+        assert_eq!(actions[0].as_tuple(), ("set", &*id, Some(other)));
+        assert!(actions.len() > 1);
+        assert!(!worksheet.check_consistency().is_empty());
+
+        // The override survives a recompute
+        worksheet.recompute_all();
+        assert_eq!(worksheet.rows[3].cells[4].val.map(char::from), Some(other));
+        // ...and a round trip through storage
+        let mut s = String::new();
+        worksheet.cells_into_str(&mut s);
+        let mut restored = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        restored.cells_from_str(&s).unwrap();
+        assert_eq!(restored, worksheet);
+        assert!(restored.rows[3].cells[4].overridden);
+
+        // ...until it is cleared
+        let actions = worksheet.clear_override(&id).unwrap();
+        assert_eq!(actions[0].as_tuple(), ("set", &*id, Some(computed.into())));
+        assert_eq!(values(&worksheet), original);
+        assert!(worksheet.check_consistency().is_empty());
+        assert!(worksheet.clear_override(&id).unwrap().is_empty());
+
+        // Input cells can't be overridden
+        let input = worksheet.rows[0].cells[0].dom_id.clone();
+        assert!(worksheet.set_override(&input, 'Q').is_err());
+    }
+
+    #[test]
+    fn fill_header() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        assert_eq!(worksheet.parse_header(), None);
+        let actions = worksheet.fill_header('2', "name", 'a').unwrap();
+        assert!(!actions.is_empty());
+        assert_eq!(worksheet.header_str(), "2NAMEA");
+        assert_eq!(
+            worksheet.parse_header(),
+            Some(('2', "NAME".to_string(), 'A'))
+        );
+
+        assert_eq!(
+            worksheet.fill_header('3', "nam", 'c').err(),
+            Some(Error::BadIdentifierLen { len: 3 }),
+        );
+        assert!(worksheet.fill_header('3', "namb", 'c').is_err());
+        assert_eq!(worksheet.header_str(), "2NAMEA");
+    }
+
+    #[test]
+    fn diagnose_failure() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        assert_eq!(worksheet.diagnose_failure(), FailureKind::Incomplete);
+        fill(&mut worksheet, SHARE_2NAMEA);
+        assert_eq!(worksheet.diagnose_failure(), FailureKind::NoFailure);
+
+        let mut corrupted =
+            Worksheet::from_share_string(SHARE_2NAMEA, Checksum::Codex32, 0).unwrap();
+        corrupted.set_data_char(10, 'Q').unwrap();
+        assert_eq!(corrupted.diagnose_failure(), FailureKind::DataError);
+
+        // BIP-173 test vector, entered into a codex32 worksheet
+        let bech32 = "abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw";
+        let mut worksheet =
+            Worksheet::new_padded("abcdef", bech32.len(), Checksum::Codex32, 0).unwrap();
+        worksheet.set_mode(CreateMode::Verify);
+        for (pos, ch) in bech32[7..].chars().enumerate() {
+            worksheet.set_data_char(pos, ch).unwrap();
+        }
+        assert!(!worksheet.is_valid());
+        assert_eq!(
+            worksheet.diagnose_failure(),
+            FailureKind::WrongChecksumType(Checksum::Bech32)
+        );
+    }
+
+    #[test]
+    fn packed_data() {
+        let mut a = Worksheet::new("ms", 15, Checksum::Bech32, 0).unwrap();
+        assert_eq!(a.packed_data(), None);
+        for (pos, ch) in "Q2NAME".chars().enumerate() {
+            a.set_data_char(pos, ch).unwrap();
+        }
+        let mut b = Worksheet::new("ms", 15, Checksum::Bech32, 1).unwrap();
+        for (pos, ch) in "Q2NAME".chars().enumerate() {
+            b.set_data_char(pos, ch).unwrap();
+        }
+        assert!(a.is_valid());
+        assert!(a.packed_data().is_some());
+        assert_eq!(a.packed_data(), b.packed_data());
+
+        b.set_data_char(5, 'C').unwrap();
+        assert_ne!(a.packed_data(), b.packed_data());
+
+        let long = Worksheet::from_share_string(SHARE_2NAMEA, Checksum::Codex32, 0).unwrap();
+        assert_eq!(long.packed_data(), None);
+    }
+
+    #[test]
+    fn focus_next_cell() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        worksheet.set_data_char(1, 'N').unwrap();
+        let actions = worksheet.handle_input_change(0, 0, "2").unwrap();
+        // The next cell is already filled in, so it is skipped
+        assert_eq!(
+            actions.last().unwrap().as_tuple(),
+            ("focus", "inp_0_0_2", None)
+        );
+        assert_eq!(worksheet.next_empty_cell(0, 0), Some((0, 2)));
+
+        // Clearing a cell doesn't move the focus
+        let actions = worksheet.handle_input_change(0, 0, "").unwrap();
+        assert!(actions.iter().all(|action| action.ty != "focus"));
+        // Nor does a bad character
+        let actions = worksheet.handle_input_change(0, 0, "b").unwrap();
+        assert!(actions.iter().all(|action| action.ty != "focus"));
+
+        // ...even into a filled cell, whose value is kept
+        worksheet.handle_input_change(0, 1, "N").unwrap();
+        for bad in ["b", "NA"] {
+            let actions = worksheet.handle_input_change(0, 1, bad).unwrap();
+            assert_eq!(
+                actions.iter().map(Action::as_tuple).collect::<Vec<_>>(),
+                vec![("flash_error", "inp_0_0_1", None)],
+            );
+            assert_eq!(worksheet.rows[0].cells[1].val.map(From::from), Some('N'));
+        }
+    }
+
+    #[test]
+    fn share_string_case() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        assert_eq!(worksheet.to_share_string_lowercase(), None);
+        fill(&mut worksheet, SHARE_2NAMEA);
+
+        let upper = worksheet.to_share_string_uppercase().unwrap();
+        let lower = worksheet.to_share_string_lowercase().unwrap();
+        assert_eq!(upper, SHARE_2NAMEA);
+        assert_eq!(lower, "ms12namea320zyxwvutsrqpnmlkjhgfedcaxrpp870hkkqrm");
+        assert_eq!(lower.to_ascii_uppercase(), upper);
+        for s in [upper, lower] {
+            assert!(Worksheet::from_share_string(&s, Checksum::Codex32, 0).is_ok());
+        }
+    }
+
+    #[test]
+    fn position_to_cell() {
+        let worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        let n_data = 48 - 3;
+        for pos in 0..n_data {
+            let (ridx, cidx) = worksheet.position_to_cell(pos).unwrap();
+            assert_eq!(worksheet.rows[ridx].cells[cidx].ty, CellType::ShareData);
+            assert_eq!(worksheet.cell_to_position(ridx, cidx), Some(pos));
+        }
+        assert_eq!(worksheet.position_to_cell(n_data), None);
+        assert_eq!(worksheet.position_to_cell(0), Some((0, 0)));
+        assert_eq!(worksheet.position_to_cell(13), Some((2, 13)));
+        assert_eq!(worksheet.position_to_cell(14), Some((2, 14)));
+        // Residue and sum cells aren't share data
+        assert_eq!(worksheet.cell_to_position(1, 0), None);
+        assert_eq!(worksheet.cell_to_position(2, 0), None);
+    }
+
+    #[test]
+    fn transposed_layout() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        fill(&mut worksheet, SHARE_2NAMEA);
+        let diagonal = worksheet.laid_out_cell_data(false, Layout::Diagonal);
+        let transposed = worksheet.laid_out_cell_data(false, Layout::Transposed);
+
+        let ids = |cells: &[DomCellData]| -> HashSet<String> {
+            cells.iter().map(|cell| cell.dom_id.clone()).collect()
+        };
+        assert_eq!(ids(&diagonal), ids(&transposed));
+        assert_eq!(diagonal.len(), transposed.len());
+        for (d, t) in diagonal.iter().zip(&transposed) {
+            assert_eq!(d.dom_id, t.dom_id);
+            assert_eq!(d.val, t.val);
+            assert_eq!((d.x, d.y), (t.y, t.x));
+        }
+        assert!(diagonal.iter().zip(&transposed).any(|(d, t)| d.x != t.x));
+    }
+
+    #[test]
+    fn from_values() {
+        let data = &SHARE_2NAMEA[3..];
+        let values: Vec<u8> = data
+            .chars()
+            .map(|ch| u8::from(Fe::try_from(ch).unwrap()))
+            .collect();
+        let worksheet = Worksheet::from_values("ms", &values, Checksum::Codex32, 0).unwrap();
+
+        let mut by_char = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        by_char.set_mode(CreateMode::Verify);
+        for (pos, ch) in data.chars().enumerate() {
+            by_char.set_data_char(pos, ch).unwrap();
+        }
+        assert_eq!(worksheet, by_char);
+        assert!(worksheet.is_valid());
+
+        let mut bad = values.clone();
+        bad[4] = 32;
+        assert_eq!(
+            Worksheet::from_values("ms", &bad, Checksum::Codex32, 0),
+            Err(Error::BadFieldValue { value: 32 })
+        );
+    }
+
+    #[test]
+    fn resize() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        fill(&mut worksheet, SHARE_2NAMEA);
+
+        let bigger = worksheet.resize(50).unwrap();
+        assert_eq!(bigger.header_str(), "2NAMEA");
+        assert!(!bigger.is_complete());
+        let smaller = bigger.resize(48).unwrap();
+        assert_eq!(smaller.to_share_string(), worksheet.to_share_string());
+        assert!(smaller.is_valid());
+
+        assert_eq!(
+            worksheet.resize(49).err(),
+            Some(Error::OddLength { data_len: 33 })
+        );
+    }
+
+    #[test]
+    fn action_history() {
+        let mut worksheet = Worksheet::new("ms", 48, Checksum::Codex32, 0).unwrap();
+        worksheet.handle_input_change(0, 0, "2").unwrap();
+        assert!(worksheet.action_history().is_empty());
+
+        worksheet.record_actions(true);
+        worksheet.handle_input_change(0, 1, "n").unwrap();
+        worksheet.handle_input_change(0, 2, "b").unwrap();
+        worksheet.handle_input_change(0, 2, "").unwrap();
+        let history: Vec<_> = worksheet
+            .action_history()
+            .iter()
+            .map(Action::as_tuple)
+            .collect();
+        // The uppercased input, then the propagated residue, then the focus
+        // move, then the rejected 'b'.
+        assert_eq!(history[0], ("flash_set", "inp_0_0_1", Some('N')));
+        assert_eq!(history[1], ("set", "inp_0_2_1", Some('Z')));
+        assert_eq!(
+            history[history.len() - 2..],
+            [
+                ("focus", "inp_0_0_2", None),
+                ("flash_error", "inp_0_0_2", None),
+            ]
+        );
+        assert!(history[1..history.len() - 2]
+            .iter()
+            .all(|act| act.0 == "set"));
+
+        let len = history.len();
+        worksheet.record_actions(false);
+        worksheet.handle_input_change(0, 3, "e").unwrap();
+        assert_eq!(worksheet.action_history().len(), len);
+        worksheet.clear_action_history();
+        assert!(worksheet.action_history().is_empty());
+    }
+
+    #[test]
+    fn separator_position() {
+        for hrp in ["ms", "bc", "tb", "bcrt"] {
+            let worksheet = Worksheet::new(hrp, 48, Checksum::Codex32, 0).unwrap();
+            assert_eq!(worksheet.separator_position(), (hrp.len(), 0));
+
+            let sep = worksheet
+                .dom_cell_data(false)
+                .into_iter()
+                .find(|cell| cell.dom_id == format!("cell_hrp_{}", hrp.len()))
+                .unwrap();
+            assert_eq!(sep.val, Some('1'));
+            assert_eq!((sep.x, sep.y), worksheet.separator_position());
+        }
+    }
+
+    #[test]
+    fn separator_char() {
+        for (checksum, size) in [
+            (Checksum::Codex32, 48),
+            (Checksum::Bech32, 17),
+            (Checksum::Codex32Long, 126),
+        ] {
+            assert_eq!(checksum.separator_char(), '1');
+            let mut worksheet = Worksheet::new("ms", size, checksum, 0).unwrap();
+            for pos in 0..size - 3 - checksum.len() {
+                worksheet.set_data_char(pos, 'X').unwrap();
+            }
+            let s = worksheet.to_share_string().unwrap();
+            assert_eq!(&s[..3], "MS1");
+
+            let sep = worksheet
+                .dom_cell_data(false)
+                .into_iter()
+                .find(|cell| (cell.x, cell.y) == worksheet.separator_position())
+                .unwrap();
+            assert_eq!(sep.text, "1");
+
+            let parsed = Worksheet::from_share_string(&s, checksum, 0).unwrap();
+            assert_eq!(parsed.to_share_string().as_deref(), Some(&*s));
+        }
+    }
+
+    #[test]
+    fn reconstruct() {
+        for &(size, checksum) in &[
+            (48, Checksum::Codex32),
+            (42, Checksum::Bech32),
+            (41, Checksum::Bech32),
+            (127, Checksum::Codex32Long),
+        ] {
+            let fresh = Worksheet::new_padded("ms", size, checksum, 0).unwrap();
+            let mut partial = fresh.clone();
+            partial.rows.clear();
+            assert!(partial.needs_reconstruction());
+            assert_eq!(partial.dom_cell_data(false), fresh.dom_cell_data(false));
+
+            let mut s = String::new();
+            fresh.cells_into_str(&mut s);
+            assert!(partial.cells_from_str(&s).is_ok());
+            assert!(!partial.needs_reconstruction());
+            assert_eq!(partial, fresh);
+        }
+    }
+
+    #[test]
+    fn numeric_rendering() {
         let mut worksheet = Worksheet::new("ms", 17, Checksum::Bech32, 0).unwrap();
-        assert_eq!(worksheet.header_str(), "______");
         assert!(worksheet.handle_input_change(0, 0, "c").is_ok());
-        assert_eq!(worksheet.header_str(), "C_____");
-        assert!(worksheet.handle_input_change(0, 1, "c").is_ok());
-        assert_eq!(worksheet.header_str(), "CC____");
-        assert!(worksheet.handle_input_change(0, 2, "c").is_ok());
-        assert!(worksheet.handle_input_change(0, 3, "c").is_ok());
-        assert!(worksheet.handle_input_change(0, 4, "c").is_ok());
-        assert_eq!(worksheet.header_str(), "CCCCC_");
 
-        assert!(worksheet.handle_input_change(2, 6, "c").is_ok());
-        assert!(worksheet.handle_input_change(2, 7, "c").is_ok());
-        assert!(worksheet.handle_input_change(0, 5, "c").is_ok());
+        let chars = worksheet.dom_cell_data(false);
+        let nums = worksheet.dom_cell_data(true);
+        assert_eq!(chars[3].dom_id, "inp_0_0_0");
+        assert_eq!(chars[3].text, "C");
+        assert_eq!(nums[3].text, "24");
+        // Only the rendered text changes
+        assert_eq!(chars[3].val, nums[3].val);
+        // Blank and non-field cells render the same either way
+        assert_eq!(nums[4].text, "");
+        assert_eq!(nums[0].text, "M");
+    }
+
+    #[test]
+    fn padded_bech32() {
+        // 33 data characters after the first row, which is odd
+        let share = "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4";
+        assert_eq!(
+            Worksheet::new("bc", share.len(), Checksum::Bech32, 0),
+            Err(Error::OddLength { data_len: 33 }),
+        );
+
+        let mut worksheet = Worksheet::new_padded("bc", share.len(), Checksum::Bech32, 0).unwrap();
+        assert_eq!(worksheet.rows[0].cells[0].ty, CellType::Padding);
+        assert_eq!(worksheet.to_share_string(), None);
+        assert!(worksheet.handle_input_change(0, 0, "p").is_ok());
+        assert_eq!(worksheet.rows[0].cells[0].val, Some(Fe::zero()));
 
-        assert_eq!(worksheet.rows[4].cells[6].val.map(From::from), Some('5'));
-        assert_eq!(worksheet.rows[4].cells[7].val.map(From::from), Some('J'));
-        assert_eq!(worksheet.rows[6].cells[6].val.map(From::from), Some('3'));
-        assert_eq!(worksheet.rows[6].cells[7].val.map(From::from), Some('C'));
-        assert_eq!(worksheet.rows[8].cells[6].val.map(From::from), Some('G'));
-        assert_eq!(worksheet.rows[8].cells[7].val.map(From::from), Some('S'));
+        fill(&mut worksheet, share);
+        assert_eq!(worksheet.to_share_string().as_deref(), Some(share));
     }
 }
@@ -24,6 +24,7 @@ pub enum Error {
     },
     BadShareDataLen {
         len: usize,
+        expected: usize,
     },
     OddLength {
         data_len: usize,
@@ -32,18 +33,111 @@ pub enum Error {
         minimum: usize,
         actual: usize,
     },
+    TooLong {
+        maximum: usize,
+        actual: usize,
+    },
     UnknownCell {
         id: String,
         reason: &'static str,
     },
+    InvalidShare {
+        idx: usize,
+        n_shares: usize,
+    },
+    RemovedShare {
+        idx: usize,
+    },
     InvalidRow {
         row: usize,
         n_rows: usize,
+        location: String,
     },
     InvalidCell {
         row: usize,
         cell: usize,
         n_cells: usize,
+        location: String,
+    },
+    BadStructure {
+        row: usize,
+        reason: &'static str,
+    },
+    InvalidPosition {
+        pos: usize,
+        n_positions: usize,
+    },
+    Json {
+        msg: String,
+    },
+    NotEditable {
+        row: usize,
+        cell: usize,
+    },
+    EmptyHrp,
+    IncompleteShare {
+        idx: usize,
+    },
+    MissingSeparator,
+    HrpMismatch {
+        expected: String,
+        actual: String,
+    },
+    BadChecksum,
+    ChecksumMismatch {
+        positions: Vec<usize>,
+    },
+    IdentifierMismatch {
+        idx: usize,
+        expected: String,
+        actual: String,
+    },
+    NotEnoughShares {
+        needed: usize,
+        have: usize,
+    },
+    BadBchCode {
+        reason: &'static str,
+    },
+    CorruptShare {
+        idx: usize,
+        data: String,
+        reason: String,
+    },
+    ThresholdMismatch {
+        idx: usize,
+        expected: usize,
+        actual: usize,
+    },
+    UnsharedSecret,
+    BadIdentifierLen {
+        len: usize,
+    },
+    BadFieldValue {
+        value: u8,
+    },
+    BadThreshold {
+        threshold: usize,
+    },
+    DuplicateShareIndex {
+        index: char,
+    },
+    SecretShareProvided,
+    UnknownChecksum {
+        name: String,
+    },
+    TooManyShares {
+        limit: usize,
+    },
+    BadCellMetadata {
+        entry: String,
+    },
+    BadShareMode {
+        mode: String,
+    },
+    BadStoredCellCount {
+        len: usize,
+        expected: usize,
     },
 }
 
@@ -53,8 +147,12 @@ impl fmt::Display for Error {
             Error::BadBech32Char { ch } => {
                 write!(f, "Bad bech32 character {}", ch)
             }
-            Error::BadShareDataLen { len } => {
-                write!(f, "Bad share data length {}", len)
+            Error::BadShareDataLen { len, expected } => {
+                write!(
+                    f,
+                    "Share data has {} characters but this share size needs {}",
+                    len, expected
+                )
             }
             Error::OddLength { data_len } => {
                 write!(
@@ -70,19 +168,155 @@ impl fmt::Display for Error {
                     actual, minimum
                 )
             }
+            Error::TooLong { maximum, actual } => {
+                write!(
+                    f,
+                    "Share size is {} but the checksum supports at most size {}",
+                    actual, maximum
+                )
+            }
             Error::UnknownCell { ref id, reason } => {
                 write!(f, "Unknown cell id {} ({})", id, reason)
             }
-            Error::InvalidRow { row, n_rows } => {
-                write!(f, "Invalid row {} (have {} rows)", row, n_rows)
+            Error::InvalidShare { idx, n_shares } => {
+                write!(f, "Invalid share {} (have {} shares)", idx, n_shares)
+            }
+            Error::RemovedShare { idx } => {
+                write!(f, "Share {} has been removed", idx)
+            }
+            Error::InvalidRow {
+                row,
+                n_rows,
+                ref location,
+            } => {
+                write!(
+                    f,
+                    "Invalid row {}, {} (have {} rows)",
+                    row, location, n_rows
+                )
             }
-            Error::InvalidCell { row, cell, n_cells } => {
+            Error::InvalidCell {
+                row,
+                n_cells,
+                ref location,
+                ..
+            } => {
                 write!(
                     f,
                     "Invalid cell {} (row {} has {} cells)",
-                    cell, row, n_cells
+                    location, row, n_cells
+                )
+            }
+            Error::BadStructure { row, reason } => {
+                write!(f, "Malformed worksheet at row {} ({})", row, reason)
+            }
+            Error::InvalidPosition { pos, n_positions } => {
+                write!(
+                    f,
+                    "Invalid share position {} (share has {} characters)",
+                    pos, n_positions
+                )
+            }
+            Error::Json { ref msg } => {
+                write!(f, "JSON serialization failed: {}", msg)
+            }
+            Error::NotEditable { row, cell } => {
+                write!(
+                    f,
+                    "Cell {} of row {} is computed and cannot be edited",
+                    cell, row
+                )
+            }
+            Error::EmptyHrp => f.write_str("HRP must not be empty"),
+            Error::IncompleteShare { idx } => write!(f, "Share {} is incomplete", idx),
+            Error::MissingSeparator => f.write_str("Share has no '1' separator"),
+            Error::HrpMismatch {
+                ref expected,
+                ref actual,
+            } => write!(f, "Share has HRP {} but expected {}", actual, expected),
+            Error::BadChecksum => f.write_str("Share checksum is invalid"),
+            Error::ChecksumMismatch { ref positions } => {
+                write!(
+                    f,
+                    "Checksum characters at positions {:?} do not match the share data",
+                    positions
+                )
+            }
+            Error::IdentifierMismatch {
+                idx,
+                ref expected,
+                ref actual,
+            } => write!(
+                f,
+                "Share {} has identifier {} but other shares have {}",
+                idx, actual, expected
+            ),
+            Error::NotEnoughShares { needed, have } => write!(
+                f,
+                "Need {} valid shares with distinct indices to recover, have {}",
+                needed, have
+            ),
+            Error::BadBchCode { reason } => write!(f, "Invalid BCH code ({})", reason),
+            Error::CorruptShare {
+                idx,
+                ref data,
+                ref reason,
+            } => write!(
+                f,
+                "Could not load share {} from {:?}: {}",
+                idx, data, reason
+            ),
+            Error::ThresholdMismatch {
+                idx,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Share {} has threshold {} but other shares have {}",
+                idx, actual, expected
+            ),
+            Error::UnsharedSecret => {
+                write!(
+                    f,
+                    "Threshold 0 means the secret is not split, so no other shares can be derived"
                 )
             }
+            Error::BadIdentifierLen { len } => {
+                write!(f, "Identifier must be 4 characters, not {}", len)
+            }
+            Error::BadFieldValue { value } => {
+                write!(
+                    f,
+                    "Value {} is out of range for a 5-bit field element",
+                    value
+                )
+            }
+            Error::BadThreshold { threshold } => {
+                write!(
+                    f,
+                    "Threshold {} cannot be encoded in a share header",
+                    threshold
+                )
+            }
+            Error::DuplicateShareIndex { index } => {
+                write!(f, "More than one complete share has index {}", index)
+            }
+            Error::SecretShareProvided => {
+                f.write_str("The secret share S is already among the shares to recover from")
+            }
+            Error::UnknownChecksum { ref name } => write!(f, "Unknown checksum {:?}", name),
+            Error::TooManyShares { limit } => {
+                write!(f, "Cannot have more than {} shares", limit)
+            }
+            Error::BadCellMetadata { ref entry } => {
+                write!(f, "Bad stored cell metadata {:?}", entry)
+            }
+            Error::BadShareMode { ref mode } => write!(f, "Unknown share mode {:?}", mode),
+            Error::BadStoredCellCount { len, expected } => write!(
+                f,
+                "Stored worksheet has {} cells but this share size needs {}",
+                len, expected
+            ),
         }
     }
 }
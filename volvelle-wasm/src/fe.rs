@@ -20,17 +20,29 @@
 #![allow(clippy::suspicious_arithmetic_impl)] // Clippy is retarded
 
 use crate::error::Error;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::{fmt, iter, ops};
 use wasm_bindgen::prelude::*;
 
 /// The checksums we support
 #[wasm_bindgen]
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Checksum {
     Codex32 = 0,
     Bech32 = 1,
+    /// The codex32 checksum for shares whose data part is longer than 93 characters
+    Codex32Long = 2,
+}
+
+thread_local! {
+    /// Cache of `Poly::hrp_residue_shifted` results, keyed by lowercase HRP, checksum and shift
+    static HRP_RESIDUE_CACHE: RefCell<HashMap<(String, Checksum, usize), Poly>> =
+        RefCell::new(HashMap::new());
 }
 
+/// The modulus defining the field GF(32) as GF(2)[x]/(x^5 + x^3 + 1), in binary
+const FIELD_POLYNOMIAL: u8 = 0b101001;
 /// Needed for indexing as we need a static-lifetime zero object
 const ZERO: Fe = Fe(0);
 /// The bech32 alphabet, in binary order
@@ -51,11 +63,273 @@ const CODEX32_POLYMOD: &[Fe] = &[
     Fe(16),
     Fe(16),
 ];
+/// The long codex32 generator polynomial
+const LONG_CODEX32_POLYMOD: &[Fe] = &[
+    Fe(15),
+    Fe(10),
+    Fe(25),
+    Fe(26),
+    Fe(9),
+    Fe(25),
+    Fe(21),
+    Fe(6),
+    Fe(23),
+    Fe(21),
+    Fe(6),
+    Fe(5),
+    Fe(22),
+    Fe(4),
+    Fe(23),
+];
 /// The bech32 generator polynomial
 const BECH32_POLYMOD: &[Fe] = &[Fe(29), Fe(22), Fe(20), Fe(21), Fe(29), Fe(18)];
 
+impl Checksum {
+    /// The numeric encoding of the checksum, equal to its enum discriminant
+    ///
+    /// This is the encoding used by the frontend. Local storage uses
+    /// `to_storage_u8` instead.
+    pub fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// The numeric encoding of the checksum in local storage
+    ///
+    /// Sessions were saved with bech32 as 0 and codex32 as 1 before the enum
+    /// discriminants were used anywhere, so this keeps that numbering to load them.
+    pub fn to_storage_u8(self) -> u8 {
+        match self {
+            Checksum::Bech32 => 0,
+            Checksum::Codex32 => 1,
+            Checksum::Codex32Long => 2,
+        }
+    }
+
+    /// Decodes a checksum from the encoding output by `to_storage_u8`
+    pub fn from_storage_u8(n: u8) -> Option<Checksum> {
+        match n {
+            0 => Some(Checksum::Bech32),
+            1 => Some(Checksum::Codex32),
+            2 => Some(Checksum::Codex32Long),
+            _ => None,
+        }
+    }
+
+    /// The character separating the HRP from the data in a share string
+    pub fn separator_char(&self) -> char {
+        match *self {
+            Checksum::Codex32 | Checksum::Bech32 | Checksum::Codex32Long => '1',
+        }
+    }
+
+    /// Removes the whitespace and dashes users add to a share to group its characters
+    ///
+    /// Whitespace is removed everywhere, but dashes only after the separator, since
+    /// they are allowed in an HRP. The remaining characters are checked when the share
+    /// is parsed.
+    pub fn normalize_share_string(&self, share: &str) -> String {
+        let share: String = share.chars().filter(|ch| !ch.is_whitespace()).collect();
+        match share.rfind(self.separator_char()) {
+            Some(sep) => {
+                let (hrp, data) = share.split_at(sep);
+                hrp.chars()
+                    .chain(data.chars().filter(|&ch| ch != '-'))
+                    .collect()
+            }
+            None => share,
+        }
+    }
+
+    /// Decodes a checksum from the encoding output by `to_u8`
+    pub fn from_u8(n: u8) -> Option<Checksum> {
+        match n {
+            0 => Some(Checksum::Codex32),
+            1 => Some(Checksum::Bech32),
+            2 => Some(Checksum::Codex32Long),
+            _ => None,
+        }
+    }
+
+    /// The generator polynomial of the checksum
+    fn modulus(&self) -> &'static [Fe] {
+        match *self {
+            Checksum::Codex32 => CODEX32_POLYMOD,
+            Checksum::Bech32 => BECH32_POLYMOD,
+            Checksum::Codex32Long => LONG_CODEX32_POLYMOD,
+        }
+    }
+
+    /// Checks that a share size is within the range the checksum supports
+    ///
+    /// BIP-93 limits the length of the data part, which follows the HRP and
+    /// separator, so the limits on the whole share depend on the HRP length.
+    pub fn check_size(&self, hrp_len: usize, size: usize) -> Result<(), Error> {
+        let prefix = hrp_len + 1;
+        let data_part_len = size.saturating_sub(prefix);
+        // Regular and long codex32 shares have disjoint sizes, with a gap between
+        match *self {
+            Checksum::Codex32 if data_part_len > 93 => Err(Error::TooLong {
+                maximum: prefix + 93,
+                actual: size,
+            }),
+            Checksum::Codex32Long if data_part_len < 96 => Err(Error::TooShort {
+                minimum: prefix + 96,
+                actual: size,
+            }),
+            Checksum::Codex32Long if data_part_len > 127 => Err(Error::TooLong {
+                maximum: prefix + 127,
+                actual: size,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether shares with this checksum start with a codex32 header: a threshold
+    /// digit, four-character identifier and share index
+    pub fn has_codex32_header(&self) -> bool {
+        match *self {
+            Checksum::Codex32 | Checksum::Codex32Long => true,
+            Checksum::Bech32 => false,
+        }
+    }
+
+    /// The number of header characters at the start of the share data
+    ///
+    /// For codex32 the header is the threshold, the four-character identifier and
+    /// the share index. The other checksums are given the same header, so that
+    /// they can be used for experimenting with share splitting.
+    pub fn header_len(&self) -> usize {
+        match *self {
+            Checksum::Codex32 | Checksum::Bech32 | Checksum::Codex32Long => 6,
+        }
+    }
+
+    /// The size of a share holding a secret of the given number of bits
+    ///
+    /// This assumes the `ms` HRP and the checksum's header. Errors if the checksum
+    /// does not support shares of that size.
+    pub fn size_for_bits(&self, bits: usize) -> Result<usize, Error> {
+        // HRP and separator, header, data and checksum
+        let size = 3 + self.header_len() + bits.div_ceil(5) + self.len();
+        self.check_size(2, size)?;
+        Ok(size)
+    }
+
+    /// The minimum Hamming distance between two valid strings, for the lengths we support
+    pub fn design_distance(&self) -> usize {
+        match *self {
+            Checksum::Codex32 | Checksum::Codex32Long => 9,
+            Checksum::Bech32 => 5,
+        }
+    }
+
+    /// How many character errors are guaranteed to be detected
+    pub fn error_detection_capacity(&self) -> usize {
+        self.design_distance() - 1
+    }
+
+    /// How many character errors can be corrected
+    pub fn error_correction_capacity(&self) -> usize {
+        (self.design_distance() - 1) / 2
+    }
+
+    /// Computes the checksum characters for a share with the given HRP and data
+    ///
+    /// The data excludes the checksum itself, which is returned.
+    pub fn compute_checksum(&self, hrp: &str, data: &[Fe]) -> Poly {
+        let zeros = iter::repeat(Fe(0)).take(self.len());
+        let residue =
+            Poly::from_hrp_and_data(hrp, data.iter().copied().chain(zeros)).checksum_polymod(*self);
+        // The checksum is linear, so adding the residue of the zeroed-out checksum
+        // to the target gives the checksum which reduces to the target
+        Poly(
+            residue
+                .iter()
+                .zip(self.target_residue().iter())
+                .map(|(a, b)| a + b)
+                .collect(),
+        )
+    }
+
+    /// The checksum as a generic BCH code
+    pub fn bch_code(&self) -> BchCode {
+        BchCode {
+            generator: Poly(self.modulus().to_vec()),
+            target: self.target_residue(),
+        }
+    }
+
+    /// The residue that a share with a valid checksum reduces to
+    pub fn target_residue(&self) -> Poly {
+        let s = match *self {
+            Checksum::Codex32 => "SECRETSHARE32",
+            Checksum::Bech32 => "QQQQQP",
+            Checksum::Codex32Long => "SECRETSHARE32EX",
+        };
+        Poly(s.chars().map(|ch| Fe::try_from(ch).unwrap()).collect())
+    }
+}
+
+impl TryFrom<&str> for Checksum {
+    type Error = Error;
+
+    /// Parses a checksum from its name, ignoring case
+    fn try_from(name: &str) -> Result<Self, Error> {
+        match name.to_ascii_lowercase().as_str() {
+            "codex32" => Ok(Checksum::Codex32),
+            "bech32" => Ok(Checksum::Bech32),
+            "codex32long" => Ok(Checksum::Codex32Long),
+            _ => Err(Error::UnknownChecksum { name: name.into() }),
+        }
+    }
+}
+
+/// A generic BCH code over the bech32 field, described by its generator polynomial
+/// and the residue that valid codewords reduce to
+///
+/// This allows experimenting with codes other than the ones in `Checksum`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BchCode {
+    /// The generator polynomial, without its leading (monic) term
+    generator: Poly,
+    /// The residue that a valid codeword reduces to
+    target: Poly,
+}
+
+impl BchCode {
+    /// Constructs a new code from a generator and target residue of the same length
+    pub fn new(generator: Poly, target: Poly) -> Result<BchCode, Error> {
+        if generator.0.is_empty() {
+            return Err(Error::BadBchCode {
+                reason: "empty generator",
+            });
+        }
+        if target.0.len() != generator.0.len() {
+            return Err(Error::BadBchCode {
+                reason: "target residue must be the same length as the generator",
+            });
+        }
+        Ok(BchCode { generator, target })
+    }
+
+    /// The generator polynomial, without its leading term
+    pub fn generator(&self) -> &Poly {
+        &self.generator
+    }
+
+    /// The residue that a valid codeword reduces to
+    pub fn target(&self) -> &Poly {
+        &self.target
+    }
+
+    /// Whether a polynomial (e.g. from `Poly::from_hrp_and_data`) reduces to the target
+    pub fn verify_checksum(&self, poly: &Poly) -> bool {
+        poly.polymod(&self.generator.0) == self.target
+    }
+}
+
 /// A single field element in the bech32 field
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Fe(u8);
 
 impl Fe {
@@ -72,6 +346,78 @@ impl Fe {
     pub fn from_bin(n: u8) -> Self {
         Fe(n)
     }
+
+    /// Iterate over all 32 elements of the field, in binary order
+    pub fn all() -> impl Iterator<Item = Fe> {
+        (0..32).map(Fe)
+    }
+
+    /// A uniformly random field element
+    ///
+    /// Panics if the system random number generator is unavailable.
+    #[cfg(feature = "rand")]
+    pub fn random() -> Self {
+        let mut byte = [0u8];
+        getrandom::getrandom(&mut byte).expect("system RNG available");
+        // 256 is a multiple of 32, so masking is unbiased
+        Fe(byte[0] & 0x1f)
+    }
+
+    /// A uniformly random nonzero field element
+    #[cfg(feature = "rand")]
+    pub fn random_nonzero() -> Self {
+        loop {
+            let fe = Fe::random();
+            if !fe.is_zero() {
+                return fe;
+            }
+        }
+    }
+
+    /// The binary expression of the field element as a two-digit decimal string
+    pub fn to_numeric_string(&self) -> String {
+        format!("{:02}", self.0)
+    }
+
+    /// Whether this is the additive identity of the field
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// The multiplicative inverse of the field element, or `None` for zero
+    pub fn inverse(&self) -> Option<Fe> {
+        if self.is_zero() {
+            return None;
+        }
+        // The multiplicative group has order 31, so x^-1 = x^30
+        let mut ret = Fe::one();
+        for _ in 0..30 {
+            ret = ret * self;
+        }
+        Some(ret)
+    }
+}
+
+/// Computes the Lagrange basis polynomials for the points `xs`, evaluated at `at`
+///
+/// Multiplying each share by the corresponding coefficient and summing yields the
+/// share with index `at`. Returns `None` if `xs` contains duplicates.
+pub fn lagrange_coefficients(xs: &[Fe], at: Fe) -> Option<Vec<Fe>> {
+    xs.iter()
+        .enumerate()
+        .map(|(i, xi)| {
+            let mut num = Fe::one();
+            let mut den = Fe::one();
+            for (j, xj) in xs.iter().enumerate() {
+                if i != j {
+                    // In characteristic 2, subtraction is addition
+                    num = num * (at + xj);
+                    den = den * (*xi + xj);
+                }
+            }
+            den.inverse().map(|inv| num * inv)
+        })
+        .collect()
 }
 
 impl From<Fe> for char {
@@ -86,6 +432,24 @@ impl fmt::Display for Fe {
     }
 }
 
+impl From<Fe> for u8 {
+    fn from(fe: Fe) -> Self {
+        fe.0
+    }
+}
+
+impl TryFrom<u8> for Fe {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Error> {
+        if value < 32 {
+            Ok(Fe(value))
+        } else {
+            Err(Error::BadFieldValue { value })
+        }
+    }
+}
+
 impl TryFrom<char> for Fe {
     type Error = Error;
 
@@ -150,6 +514,10 @@ impl ops::Mul<Fe> for Fe {
 impl ops::Mul<&Fe> for Fe {
     type Output = Fe;
     fn mul(mut self, other: &Fe) -> Fe {
+        if self.is_zero() || other.is_zero() {
+            return Fe::zero();
+        }
+
         let mut ret = 0;
         let mut fe2 = other.0;
         while self.0 > 0 {
@@ -160,16 +528,29 @@ impl ops::Mul<&Fe> for Fe {
             self.0 >>= 1;
             fe2 <<= 1;
 
+            // Reduce by the field polynomial whenever we overflow into x^5
             if fe2 & 32 == 32 {
-                fe2 ^= 32 + 8 + 1;
+                fe2 ^= FIELD_POLYNOMIAL;
             }
         }
         Fe(ret)
     }
 }
 
+/// How many characters of a string must change to make its checksum valid, as far
+/// as `Poly::distance_to_valid` searches
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DistanceToValid {
+    /// The checksum is already valid
+    Valid,
+    /// Changing a single character would make the checksum valid
+    OneError,
+    /// At least two characters must change; the exact distance is not computed
+    AtLeastTwo,
+}
+
 /// A polynomial in the bech32 field
-#[derive(Clone, PartialEq, Eq, Debug, Default)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
 pub struct Poly(Vec<Fe>);
 
 impl fmt::Display for Poly {
@@ -181,6 +562,12 @@ impl fmt::Display for Poly {
     }
 }
 
+impl From<Vec<Fe>> for Poly {
+    fn from(v: Vec<Fe>) -> Self {
+        Poly(v)
+    }
+}
+
 impl From<Fe> for Poly {
     fn from(fe: Fe) -> Self {
         Poly(vec![fe])
@@ -195,39 +582,52 @@ impl ops::Index<usize> for Poly {
 }
 
 impl Poly {
-    /*
-    /// Helper function that drops any leading 0s from the polynomial
-    fn normalize(&mut self) {
-        let mut seen_nonzero = false;
-        self.0.retain(|&elem| {
-            if elem != Fe(0) {
-                seen_nonzero = true;
-            }
-            seen_nonzero
-        });
+    /// A copy of the polynomial with any leading 0s dropped
+    ///
+    /// Residues must keep their leading 0s, so this never modifies the polynomial
+    /// in place.
+    pub fn normalized(&self) -> Self {
+        let start = self
+            .0
+            .iter()
+            .position(|fe| !fe.is_zero())
+            .unwrap_or(self.0.len());
+        Poly(self.0[start..].to_vec())
     }
-    */
 
-    /// Reduce a polynomial modulo the codex32 generator polynomial
-    fn polymod(&self, modulus: &[Fe]) -> Self {
-        let mut ret = vec![Fe(0); modulus.len()];
+    /// The highest-degree nonzero coefficient, or `None` for the zero polynomial
+    pub fn leading_coefficient(&self) -> Option<Fe> {
+        self.0.iter().copied().find(|fe| !fe.is_zero())
+    }
 
-        for ch in &self.0 {
-            // Multiply residue by x
-            let c13 = ret[0];
-            for i in 0..modulus.len() - 1 {
-                ret[i] = ret[i + 1];
-            }
-            // Add next character
-            ret[modulus.len() - 1] = *ch;
-            // Replace A*x^13 by A*polymod
-            for i in 0..modulus.len() {
-                ret[i] = ret[i] + c13 * modulus[i];
-            }
+    /// Evaluates the polynomial at a point
+    pub fn evaluate(&self, x: Fe) -> Fe {
+        self.0.iter().fold(Fe::zero(), |acc, fe| acc * x + fe)
+    }
+
+    /// Reduce a polynomial modulo an arbitrary generator polynomial, given without
+    /// its leading term
+    ///
+    /// Errors if the modulus is empty, since there is nothing to reduce by.
+    pub fn polymod_by(&self, modulus: &Poly) -> Result<Self, Error> {
+        if modulus.0.is_empty() {
+            return Err(Error::BadBchCode {
+                reason: "empty generator",
+            });
         }
+        Ok(self.polymod(&modulus.0))
+    }
 
-        // ret.normalize(); // Don't normalize!! We need to keep trailing 0s.
-        Poly(ret)
+    /// Reduce a polynomial modulo a generator polynomial
+    ///
+    /// The modulus must be nonempty; use `polymod_by` for untrusted moduli.
+    fn polymod(&self, modulus: &[Fe]) -> Self {
+        let mut engine = StreamingChecksum::with_modulus(modulus);
+        for ch in &self.0 {
+            engine.push(*ch);
+        }
+        // Don't normalize!! We need to keep trailing 0s.
+        engine.current()
     }
 
     /// Reduce a polynomial modulo the codex32 generator polynomial
@@ -240,6 +640,11 @@ impl Poly {
         self.polymod(BECH32_POLYMOD)
     }
 
+    /// Reduce a polynomial modulo the generator polynomial of the given checksum
+    pub fn checksum_polymod(&self, checksum: Checksum) -> Self {
+        self.polymod(checksum.modulus())
+    }
+
     /// Shift the polynomial left a number of spaces
     pub fn mul_by_x(&mut self, n: usize) {
         self.0.extend(iter::repeat(Fe(0)).take(n));
@@ -250,41 +655,390 @@ impl Poly {
         self.0.push(fe);
     }
 
-    /// Convert a HRP into a polynomial
-    fn hrp_residue(s: &str, modulus: &[Fe]) -> Self {
-        let mut poly_1 = Vec::with_capacity(s.len() * 2 + modulus.len() + 2);
-        poly_1.push(Fe(1));
+    /// Expand a HRP into the polynomial which prefixes the share data
+    fn hrp_expansion(s: &str, extra_capacity: usize) -> Vec<Fe> {
+        let mut ret = Vec::with_capacity(s.len() * 2 + extra_capacity + 2);
+        ret.push(Fe(1));
         for ch in s.bytes() {
-            poly_1.push(Fe(ch.to_ascii_lowercase() >> 5));
+            ret.push(Fe(ch.to_ascii_lowercase() >> 5));
         }
-        poly_1.push(Fe(0));
+        ret.push(Fe(0));
         for ch in s.bytes() {
-            poly_1.push(Fe(ch.to_ascii_lowercase() & 0x1f));
+            ret.push(Fe(ch.to_ascii_lowercase() & 0x1f));
         }
-        poly_1.extend(iter::repeat(Fe(0)).take(modulus.len()));
+        ret
+    }
+
+    /// Convert a HRP into a polynomial, shifted left by `shift` places
+    fn hrp_residue(s: &str, modulus: &[Fe], shift: usize) -> Self {
+        let mut poly_1 = Poly::hrp_expansion(s, shift);
+        poly_1.extend(iter::repeat(Fe(0)).take(shift));
         Poly(poly_1).polymod(modulus)
     }
 
+    /// Construct the polynomial whose residue is checked for a share with the
+    /// given HRP and data (including the checksum)
+    pub fn from_hrp_and_data<I: IntoIterator<Item = Fe>>(hrp: &str, data: I) -> Self {
+        let mut ret = Poly::hrp_expansion(hrp, 0);
+        ret.extend(data);
+        Poly(ret)
+    }
+
+    /// Parses the data and checksum characters of a share string, dropping the HRP and
+    /// separator
+    ///
+    /// The HRP is not part of the result; pass it back through `from_hrp_and_data` to
+    /// obtain a polynomial which can be checked with `BchCode::verify_checksum`.
+    /// Whitespace and dashes used to group the share's characters are ignored.
+    pub fn from_share_string(s: &str, checksum: Checksum) -> Result<Self, Error> {
+        let s = &checksum.normalize_share_string(s);
+        let sep_char = checksum.separator_char();
+        let sep = s.rfind(sep_char).ok_or(Error::MissingSeparator)?;
+        let data = &s[sep + sep_char.len_utf8()..];
+        if data.len() < checksum.len() {
+            return Err(Error::TooShort {
+                minimum: checksum.len(),
+                actual: data.len(),
+            });
+        }
+        data.chars()
+            .map(|ch| Fe::try_from(ch.to_ascii_uppercase()))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Poly)
+    }
+
+    /// Bounds the Hamming distance from this polynomial to the nearest valid codeword
+    ///
+    /// The polynomial should include the HRP, e.g. as constructed by `from_hrp_and_data`.
+    /// Only single-character changes are searched, so anything further away is
+    /// reported as `AtLeastTwo`.
+    pub fn distance_to_valid(&self, checksum: Checksum) -> DistanceToValid {
+        match self.single_error(checksum) {
+            Some((_, e)) if e.is_zero() => DistanceToValid::Valid,
+            Some(_) => DistanceToValid::OneError,
+            None => DistanceToValid::AtLeastTwo,
+        }
+    }
+
+    /// Finds the single error, if any, which would explain this polynomial's residue
+    ///
+    /// Returns the position counted from the start of the polynomial, and the value
+    /// that position should have for the checksum to be valid. Returns `None` if the
+    /// checksum is valid or if more than one character must be wrong.
+    pub fn correct_single_error(&self, checksum: Checksum) -> Option<(usize, Fe)> {
+        match self.single_error(checksum) {
+            Some((j, e)) if !e.is_zero() => {
+                let pos = self.0.len() - 1 - j;
+                Some((pos, self.0[pos] + e))
+            }
+            _ => None,
+        }
+    }
+
+    /// Finds a position `j`, counted from the end of the polynomial, and an error `e`
+    /// such that adding `e * x^j` makes the checksum valid
+    ///
+    /// Returns `(0, 0)` if the checksum is already valid.
+    fn single_error(&self, checksum: Checksum) -> Option<(usize, Fe)> {
+        let modulus = checksum.modulus();
+        let target = checksum.target_residue();
+        // The syndrome is the difference between the actual and target residues
+        let syndrome: Vec<Fe> = self
+            .polymod(modulus)
+            .iter()
+            .zip(target.iter())
+            .map(|(a, b)| a + b)
+            .collect();
+        if syndrome.iter().all(Fe::is_zero) {
+            return Some((0, Fe::zero()));
+        }
+
+        // An error of e at the jth-from-last position changes the residue by e * x^j
+        let mut x_j = StreamingChecksum::with_modulus(modulus);
+        x_j.push(Fe::one());
+        for j in 0..self.0.len() {
+            let x_j_res = x_j.current();
+            for e in 1..32 {
+                if x_j_res.scale(Fe(e)).0 == syndrome {
+                    return Some((j, Fe(e)));
+                }
+            }
+            x_j.push(Fe::zero());
+        }
+        None
+    }
+
     /// Convert a HRP into a polynomial residue (codex32)
     pub fn codex32_hrp_residue(s: &str) -> Self {
-        Poly::hrp_residue(s, CODEX32_POLYMOD)
+        Poly::hrp_residue(s, CODEX32_POLYMOD, CODEX32_POLYMOD.len())
     }
 
     /// Convert a HRP into a polynomial residue (bech32)
     pub fn bech32_hrp_residue(s: &str) -> Self {
-        Poly::hrp_residue(s, BECH32_POLYMOD)
+        Poly::hrp_residue(s, BECH32_POLYMOD, BECH32_POLYMOD.len())
+    }
+
+    /// Convert a HRP into a polynomial residue for the given checksum, shifted
+    /// left by `shift` places rather than the length of the checksum
+    pub fn hrp_residue_shifted(s: &str, checksum: Checksum, shift: usize) -> Self {
+        Poly::hrp_residue(s, checksum.modulus(), shift)
+    }
+
+    /// As `hrp_residue_shifted`, but remembers the result for future calls
+    ///
+    /// A session typically creates many worksheets with the same HRP, so this
+    /// avoids recomputing the same residue for each one.
+    pub fn hrp_residue_cached(s: &str, checksum: Checksum, shift: usize) -> Self {
+        let key = (s.to_ascii_lowercase(), checksum, shift);
+        HRP_RESIDUE_CACHE.with(|cache| {
+            cache
+                .borrow_mut()
+                .entry(key)
+                .or_insert_with(|| Poly::hrp_residue_shifted(s, checksum, shift))
+                .clone()
+        })
+    }
+
+    /// Appends the coefficients of `other` after those of this polynomial
+    ///
+    /// This is `self * x^n + other` where `n` is the length of `other`, so e.g. the
+    /// HRP expansion concatenated with the share data is the polynomial checked
+    /// by the checksum.
+    pub fn concat(&self, other: &Poly) -> Poly {
+        let mut ret = self.clone();
+        ret.mul_by_x(other.0.len());
+        let offset = ret.0.len() - other.0.len();
+        for (n, fe) in other.iter().enumerate() {
+            ret.0[offset + n] = ret.0[offset + n] + fe;
+        }
+        ret
     }
 
     /// Return an iterator over the coefficients of the polynomial
     pub fn iter(&self) -> impl Iterator<Item = Fe> + '_ {
         self.0.iter().copied()
     }
+
+    /// Multiply every coefficient of the polynomial by a scalar
+    ///
+    /// Preserves the length of the polynomial, including any leading zeros.
+    pub fn scale(&self, k: Fe) -> Poly {
+        Poly(self.0.iter().map(|fe| k * fe).collect())
+    }
+
+    /// Keep only the `n` lowest-order coefficients of the polynomial
+    ///
+    /// Since coefficients are stored highest-degree first, this keeps the last `n`
+    /// of them, adding leading zeros if the polynomial is shorter than `n`.
+    pub fn truncate_to(&self, n: usize) -> Poly {
+        let len = self.0.len();
+        if len >= n {
+            Poly(self.0[len - n..].to_vec())
+        } else {
+            let mut ret = Vec::with_capacity(n);
+            ret.extend(iter::repeat(Fe(0)).take(n - len));
+            ret.extend_from_slice(&self.0);
+            Poly(ret)
+        }
+    }
+}
+
+/// A checksum residue which is computed incrementally, one character at a time
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StreamingChecksum<'a> {
+    modulus: &'a [Fe],
+    residue: Vec<Fe>,
+}
+
+impl StreamingChecksum<'static> {
+    /// Construct a new checksum engine with a zero residue
+    pub fn new(checksum: Checksum) -> Self {
+        StreamingChecksum::with_modulus(checksum.modulus())
+    }
+}
+
+impl<'a> StreamingChecksum<'a> {
+    fn with_modulus(modulus: &'a [Fe]) -> Self {
+        assert!(
+            !modulus.is_empty(),
+            "checksum modulus must have at least one coefficient"
+        );
+        StreamingChecksum {
+            modulus,
+            residue: vec![Fe(0); modulus.len()],
+        }
+    }
+
+    /// Multiply the residue by x, add a new character, and reduce
+    pub fn push(&mut self, fe: Fe) {
+        let len = self.modulus.len();
+        // Multiply residue by x
+        let c13 = self.residue[0];
+        for i in 0..len - 1 {
+            self.residue[i] = self.residue[i + 1];
+        }
+        // Add next character
+        self.residue[len - 1] = fe;
+        // Replace A*x^13 by A*polymod
+        for i in 0..len {
+            self.residue[i] = self.residue[i] + c13 * self.modulus[i];
+        }
+    }
+
+    /// The residue of all the characters pushed so far
+    pub fn current(&self) -> Poly {
+        Poly(self.residue.clone())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn compute_checksum() {
+        let share = "MS12NAMEA320ZYXWVUTSRQPNMLKJHGFEDCAXRPP870HKKQRM";
+        let fes = Poly::from_share_string(share, Checksum::Codex32).unwrap();
+        let fes: Vec<Fe> = fes.iter().collect();
+        let (data, _) = fes.split_at(fes.len() - 13);
+        assert_eq!(
+            Checksum::Codex32.compute_checksum("ms", data).to_string(),
+            &share[35..],
+        );
+    }
+
+    #[test]
+    fn concat() {
+        let hrp = Poly::from_hrp_and_data("ms", iter::empty());
+        let share = Poly::from_share_string(
+            "MS12NAMEA320ZYXWVUTSRQPNMLKJHGFEDCAXRPP870HKKQRM",
+            Checksum::Codex32,
+        )
+        .unwrap();
+        let full = hrp.concat(&share);
+        assert_eq!(full, Poly::from_hrp_and_data("ms", share.iter()));
+        assert!(Checksum::Codex32.bch_code().verify_checksum(&full));
+
+        let a = Poly(vec![Fe(1), Fe(2)]);
+        let b = Poly(vec![Fe(3)]);
+        assert_eq!(a.concat(&b).to_string(), "PZR");
+        assert_eq!(b.concat(&a).to_string(), "RPZ");
+        assert_eq!(a.concat(&Poly(vec![])), a);
+        assert_eq!(Poly(vec![]).concat(&a), a);
+    }
+
+    #[test]
+    fn size_for_bits() {
+        assert_eq!(Checksum::Codex32.size_for_bits(128), Ok(48));
+        assert_eq!(Checksum::Codex32.size_for_bits(256), Ok(74));
+        // Sizes round up to a whole character
+        assert_eq!(Checksum::Codex32.size_for_bits(130), Ok(48));
+        assert_eq!(Checksum::Codex32.size_for_bits(131), Ok(49));
+
+        assert_eq!(
+            Checksum::Codex32.size_for_bits(512),
+            Err(Error::TooLong {
+                maximum: 96,
+                actual: 125,
+            }),
+        );
+        assert_eq!(Checksum::Codex32Long.size_for_bits(512), Ok(127));
+        assert_eq!(
+            Checksum::Codex32Long.size_for_bits(128),
+            Err(Error::TooShort {
+                minimum: 99,
+                actual: 50,
+            }),
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn random() {
+        let mut seen = [false; 32];
+        for _ in 0..2000 {
+            let fe = Fe::random();
+            assert!(u8::from(fe) < 32);
+            seen[usize::from(u8::from(fe))] = true;
+
+            let fe = Fe::random_nonzero();
+            assert!(!fe.is_zero());
+            assert!(u8::from(fe) < 32);
+        }
+        // Failure probability is around 32 * (31/32)^2000, negligible
+        assert!(seen.iter().all(|&s| s));
+    }
+
+    #[test]
+    fn fe_u8() {
+        for n in 0..32 {
+            assert_eq!(u8::from(Fe::from_bin(n)), n);
+            assert_eq!(Fe::try_from(n).map(u8::from), Ok(n));
+        }
+        for fe in Fe::all() {
+            assert_eq!(Fe::from_bin(u8::from(fe)), fe);
+        }
+    }
+
+    #[test]
+    fn checksum_u8() {
+        for checksum in [Checksum::Codex32, Checksum::Bech32, Checksum::Codex32Long] {
+            assert_eq!(Checksum::from_u8(checksum.to_u8()), Some(checksum));
+        }
+        assert_eq!(Checksum::Codex32.to_u8(), 0);
+        assert_eq!(Checksum::Bech32.to_u8(), 1);
+        assert_eq!(Checksum::from_u8(3), None);
+
+        for checksum in [Checksum::Codex32, Checksum::Bech32, Checksum::Codex32Long] {
+            assert_eq!(
+                Checksum::from_storage_u8(checksum.to_storage_u8()),
+                Some(checksum)
+            );
+        }
+        assert_eq!(Checksum::Bech32.to_storage_u8(), 0);
+        assert_eq!(Checksum::Codex32.to_storage_u8(), 1);
+        assert_eq!(Checksum::from_storage_u8(3), None);
+    }
+
+    #[test]
+    fn polymod_by() {
+        let poly = Poly(vec![Fe(2), Fe(8), Fe(0), Fe(0), Fe(0), Fe(0), Fe(0), Fe(0)]);
+        let bech32 = Checksum::Bech32.bch_code();
+        assert_eq!(
+            poly.polymod_by(bech32.generator()),
+            Ok(poly.bech32_polymod())
+        );
+        assert_eq!(
+            poly.polymod_by(&Poly(vec![])),
+            Err(Error::BadBchCode {
+                reason: "empty generator"
+            })
+        );
+        // A degree-1 generator is degenerate but fine
+        assert_eq!(
+            poly.polymod_by(&Poly(vec![Fe(1)])).unwrap().iter().count(),
+            1
+        );
+    }
+
+    #[test]
+    fn normalized() {
+        let poly = Poly(vec![Fe(0), Fe(0), Fe(3), Fe(0), Fe(7)]);
+        assert_eq!(poly.normalized(), Poly(vec![Fe(3), Fe(0), Fe(7)]));
+        assert_eq!(poly.leading_coefficient(), Some(Fe(3)));
+        assert_eq!(poly.normalized().leading_coefficient(), Some(Fe(3)));
+        for x in Fe::all() {
+            assert_eq!(poly.normalized().evaluate(x), poly.evaluate(x));
+        }
+        // The original is untouched
+        assert_eq!(poly.iter().count(), 5);
+
+        let zero = Poly(vec![Fe(0); 4]);
+        assert_eq!(zero.normalized(), Poly(vec![]));
+        assert_eq!(zero.leading_coefficient(), None);
+        assert_eq!(zero.evaluate(Fe(9)), Fe::zero());
+    }
+
     #[test]
     fn polymod() {
         assert_eq!(Poly::codex32_hrp_residue("ms").to_string(), "33XW87RR3YLJG");
@@ -298,14 +1052,347 @@ mod tests {
         assert_eq!(zg.bech32_polymod().to_string(), "Q863G3");
     }
 
+    #[test]
+    fn distance_to_valid() {
+        let data = "2NAMEA320ZYXWVUTSRQPNMLKJHGFEDCAXRPP870HKKQRM";
+        let mut fes: Vec<Fe> = data.chars().map(|ch| Fe::try_from(ch).unwrap()).collect();
+        let poly = Poly::from_hrp_and_data("ms", fes.iter().copied());
+        assert_eq!(
+            poly.distance_to_valid(Checksum::Codex32),
+            DistanceToValid::Valid
+        );
+        assert_ne!(
+            poly.distance_to_valid(Checksum::Bech32),
+            DistanceToValid::Valid
+        );
+
+        for i in 0..fes.len() {
+            let orig = fes[i];
+            fes[i] = orig + Fe(7);
+            let poly = Poly::from_hrp_and_data("ms", fes.iter().copied());
+            assert_eq!(
+                poly.distance_to_valid(Checksum::Codex32),
+                DistanceToValid::OneError
+            );
+            fes[i] = orig;
+        }
+
+        fes[0] = fes[0] + Fe(1);
+        fes[10] = fes[10] + Fe(1);
+        let poly = Poly::from_hrp_and_data("ms", fes.iter().copied());
+        assert_eq!(
+            poly.distance_to_valid(Checksum::Codex32),
+            DistanceToValid::AtLeastTwo
+        );
+
+        let data = "QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4";
+        let poly = Poly::from_hrp_and_data("bc", data.chars().map(|ch| Fe::try_from(ch).unwrap()));
+        assert_eq!(
+            poly.distance_to_valid(Checksum::Bech32),
+            DistanceToValid::Valid
+        );
+    }
+
+    #[test]
+    fn long_codex32() {
+        // BIP-93 test vector 5
+        let data = "00C8VSM32ZXFGUHPCHTLUPZRY9X8GF2TVDW0S3JN54KHCE6MUA7LQPZYGSFJD6AN074RXVCEMLH8WU3TK925ACDEFGHJKLMNPQRSTUVWXY06FHPV80UNDVARHRAK";
+        let poly = Poly::from_hrp_and_data("ms", data.chars().map(|ch| Fe::try_from(ch).unwrap()));
+        assert_eq!(
+            poly.checksum_polymod(Checksum::Codex32Long),
+            Checksum::Codex32Long.target_residue()
+        );
+        assert_eq!(
+            poly.distance_to_valid(Checksum::Codex32Long),
+            DistanceToValid::Valid
+        );
+        assert_ne!(
+            poly.distance_to_valid(Checksum::Codex32),
+            DistanceToValid::Valid
+        );
+    }
+
+    #[test]
+    fn scale() {
+        let poly = Poly::codex32_hrp_residue("ms");
+        assert_eq!(poly.scale(Fe::one()), poly);
+        assert_eq!(poly.scale(Fe::zero()), Poly(vec![Fe(0); 13]));
+        assert_eq!(poly.scale(Fe(2)).scale(Fe(3)), poly.scale(Fe(6)));
+    }
+
+    #[test]
+    fn from_share_string() {
+        let share = "MS12NAMEA320ZYXWVUTSRQPNMLKJHGFEDCAXRPP870HKKQRM";
+        let poly = Poly::from_share_string(share, Checksum::Codex32).unwrap();
+        assert_eq!(poly.iter().count(), 45);
+        assert_eq!(poly.0[0], Fe::try_from('2').unwrap());
+        assert_eq!(
+            Poly::from_share_string(&share.to_ascii_lowercase(), Checksum::Codex32),
+            Ok(poly.clone()),
+        );
+        assert_eq!(
+            Poly::from_share_string(
+                "ms1 2NAM-EA32 0ZYX-WVUT\nSRQP-NMLK-JHGF-EDCA-XRPP-870H-KKQRM",
+                Checksum::Codex32
+            ),
+            Ok(poly.clone()),
+        );
+
+        let full = Poly::from_hrp_and_data("ms", poly.iter());
+        assert!(Checksum::Codex32.bch_code().verify_checksum(&full));
+
+        assert_eq!(
+            Poly::from_share_string("MS2NAMEA320ZYXW", Checksum::Codex32),
+            Err(Error::MissingSeparator),
+        );
+        assert!(Poly::from_share_string("MS12NAMEA", Checksum::Codex32).is_err());
+        assert!(Poly::from_share_string(
+            "MS12NAMEB320ZYXWVUTSRQPNMLKJHGFEDCAXRPP870HKKQRM",
+            Checksum::Codex32
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn hrp_residue_cached() {
+        for hrp in ["ms", "MS", "cl", "bc", "a", "longerhrp"] {
+            for _ in 0..2 {
+                assert_eq!(
+                    Poly::hrp_residue_cached(hrp, Checksum::Codex32, 13),
+                    Poly::codex32_hrp_residue(hrp),
+                );
+                assert_eq!(
+                    Poly::hrp_residue_cached(hrp, Checksum::Bech32, 6),
+                    Poly::bech32_hrp_residue(hrp),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn correct_single_error() {
+        let data = "2NAMEA320ZYXWVUTSRQPNMLKJHGFEDCAXRPP870HKKQRM";
+        let data: Vec<Fe> = data.chars().map(|ch| Fe::try_from(ch).unwrap()).collect();
+        let valid = Poly::from_hrp_and_data("ms", data.clone());
+        assert_eq!(valid.correct_single_error(Checksum::Codex32), None);
+
+        let offset = valid.0.len() - data.len();
+        for pos in [0, 7, 44] {
+            let mut corrupted = data.clone();
+            corrupted[pos] = corrupted[pos] + Fe(3);
+            let poly = Poly::from_hrp_and_data("ms", corrupted);
+            assert_eq!(
+                poly.correct_single_error(Checksum::Codex32),
+                Some((offset + pos, data[pos])),
+            );
+        }
+    }
+
+    #[test]
+    fn error_capacity() {
+        assert_eq!(Checksum::Codex32.error_detection_capacity(), 8);
+        assert_eq!(Checksum::Codex32.error_correction_capacity(), 4);
+        assert_eq!(Checksum::Codex32Long.error_correction_capacity(), 4);
+        assert_eq!(Checksum::Bech32.error_detection_capacity(), 4);
+        assert_eq!(Checksum::Bech32.error_correction_capacity(), 2);
+    }
+
+    #[test]
+    fn bch_code() {
+        let to_poly = |s: &str| Poly(s.chars().map(|ch| Fe::try_from(ch).unwrap()).collect());
+        let code = BchCode::new(Poly(CODEX32_POLYMOD.to_vec()), to_poly("SECRETSHARE32")).unwrap();
+        assert_eq!(code, Checksum::Codex32.bch_code());
+
+        let valid = Poly::from_hrp_and_data(
+            "ms",
+            to_poly("2NAMEA320ZYXWVUTSRQPNMLKJHGFEDCAXRPP870HKKQRM").0,
+        );
+        let invalid = Poly::from_hrp_and_data(
+            "ms",
+            to_poly("2NAMEA320ZYXWVUTSRQPNMLKJHGFEDCAXRPP870HKKQRQ").0,
+        );
+        assert!(code.verify_checksum(&valid));
+        assert!(!code.verify_checksum(&invalid));
+        // A different target accepts different codewords
+        let other = BchCode::new(code.generator().clone(), to_poly("QQQQQQQQQQQQQ")).unwrap();
+        assert!(!other.verify_checksum(&valid));
+
+        assert!(BchCode::new(Poly(vec![]), Poly(vec![])).is_err());
+        assert!(BchCode::new(Poly(CODEX32_POLYMOD.to_vec()), to_poly("QQQQQP")).is_err());
+    }
+
+    #[test]
+    fn field_polynomial() {
+        // x^5 = x^3 + 1
+        let x = Fe(2);
+        let x5 = (0..5).fold(Fe::one(), |acc, _| acc * x);
+        assert_eq!(x5, Fe(8) + Fe(1));
+        assert_eq!(FIELD_POLYNOMIAL, 32 + 8 + 1);
+
+        // x is primitive, i.e. has multiplicative order 31, so the field is GF(32)
+        let mut seen = std::collections::HashSet::new();
+        let mut power = Fe::one();
+        for _ in 0..31 {
+            assert!(seen.insert(power));
+            power = power * x;
+        }
+        assert_eq!(power, Fe::one());
+        assert_eq!(seen.len(), 31);
+        assert!(!seen.contains(&Fe::zero()));
+    }
+
+    #[test]
+    fn inverse() {
+        assert_eq!(Fe::zero().inverse(), None);
+        for fe in Fe::all().skip(1) {
+            assert_eq!(fe * fe.inverse().unwrap(), Fe::one());
+        }
+    }
+
+    #[test]
+    fn lagrange_coefficients() {
+        let xs = [Fe(1), Fe(5), Fe(20)];
+        let ls = super::lagrange_coefficients(&xs, Fe(16)).unwrap();
+        // The coefficients interpolate constants...
+        assert_eq!(ls.iter().fold(Fe::zero(), |acc, l| acc + l), Fe::one());
+        // ...and evaluating at one of the points picks out that point
+        let ls = super::lagrange_coefficients(&xs, Fe(5)).unwrap();
+        assert_eq!(ls, [Fe(0), Fe(1), Fe(0)]);
+        assert_eq!(super::lagrange_coefficients(&[Fe(1), Fe(1)], Fe(16)), None);
+    }
+
+    #[test]
+    fn hash() {
+        use std::collections::HashSet;
+
+        let set: HashSet<Fe> = "QPZRY9X8GF2TVDW0S3JN54KHCE6MUA7LQPZRY"
+            .chars()
+            .map(|ch| Fe::try_from(ch).unwrap())
+            .collect();
+        assert_eq!(set.len(), 32);
+
+        let set: HashSet<Poly> = ["ms", "MS", "bc", "tb"]
+            .iter()
+            .map(|hrp| Poly::codex32_hrp_residue(hrp))
+            .collect();
+        assert_eq!(set.len(), 3);
+    }
+
+    #[test]
+    fn truncate_to() {
+        let poly = Poly(vec![Fe(1), Fe(2), Fe(3)]);
+        assert_eq!(poly.truncate_to(2), Poly(vec![Fe(2), Fe(3)]));
+        assert_eq!(poly.truncate_to(3), poly);
+        assert_eq!(
+            poly.truncate_to(5),
+            Poly(vec![Fe(0), Fe(0), Fe(1), Fe(2), Fe(3)])
+        );
+        assert_eq!(poly.truncate_to(0), Poly(vec![]));
+
+        // Shifting a residue then truncating is multiplication mod x^n
+        let mut shifted = poly.clone();
+        shifted.mul_by_x(2);
+        assert_eq!(shifted.truncate_to(3), Poly(vec![Fe(3), Fe(0), Fe(0)]));
+    }
+
+    #[test]
+    fn streaming_checksum() {
+        let to_poly = |s: &str| Poly(s.chars().map(|ch| Fe::try_from(ch).unwrap()).collect());
+        let stream = |checksum: Checksum, hrp: &str, data: &str| {
+            let mut engine = StreamingChecksum::new(checksum);
+            assert_eq!(engine.current(), Poly(vec![Fe(0); checksum.len()]));
+            for fe in Poly::hrp_expansion(hrp, 0)
+                .into_iter()
+                .chain(to_poly(data).0)
+            {
+                engine.push(fe);
+            }
+            engine.current()
+        };
+
+        // A valid codex32 share streams to the codex32 target residue
+        assert_eq!(
+            stream(
+                Checksum::Codex32,
+                "ms",
+                "2NAMEA320ZYXWVUTSRQPNMLKJHGFEDCAXRPP870HKKQRM"
+            ),
+            to_poly("SECRETSHARE32"),
+        );
+        // ...and a valid bech32 string to the bech32 constant, 1
+        assert_eq!(
+            stream(
+                Checksum::Bech32,
+                "abcdef",
+                "QPZRY9X8GF2TVDW0S3JN54KHCE6MUA7LMQQQXW"
+            ),
+            to_poly("QQQQQP"),
+        );
+    }
+
+    #[test]
+    fn mul_zero() {
+        // The multiplication algorithm, without the zero short-circuit
+        fn slow_mul(mut a: u8, mut b: u8) -> u8 {
+            let mut ret = 0;
+            while a > 0 {
+                if a & 1 == 1 {
+                    ret ^= b;
+                }
+                a >>= 1;
+                b <<= 1;
+                if b & 32 == 32 {
+                    b ^= 32 + 8 + 1;
+                }
+            }
+            ret
+        }
+
+        for x in 0..32 {
+            assert_eq!(Fe::zero() * Fe(x), Fe::zero());
+            assert_eq!(Fe(x) * Fe::zero(), Fe::zero());
+            for y in 0..32 {
+                assert_eq!(Fe(x) * Fe(y), Fe(slow_mul(x, y)));
+            }
+        }
+    }
+
     #[test]
     fn rtt_fe() {
         for ch in "ACDEFGHJKLMNPQRSTUVWXYZ0234567890".chars() {
             assert_eq!(ch, Fe::try_from(ch).unwrap().into());
         }
 
-        for n in 0..32 {
-            assert_eq!(Fe(n), Fe::try_from(char::from(Fe(n))).unwrap());
+        for fe in Fe::all() {
+            assert_eq!(fe, Fe::try_from(char::from(fe)).unwrap());
         }
     }
+
+    #[test]
+    fn all() {
+        assert_eq!(Fe::all().count(), 32);
+        let chars: String = Fe::all().map(char::from).collect();
+        assert_eq!(chars, "QPZRY9X8GF2TVDW0S3JN54KHCE6MUA7L");
+        assert_eq!(Fe::all().next(), Some(Fe::zero()));
+    }
+
+    #[test]
+    fn header_len() {
+        assert_eq!(Checksum::Codex32.header_len(), 6);
+        assert_eq!(Checksum::Codex32Long.header_len(), 6);
+        assert_eq!(Checksum::Bech32.header_len(), 6);
+    }
+
+    #[test]
+    fn checksum_try_from_str() {
+        assert_eq!(Checksum::try_from("codex32"), Ok(Checksum::Codex32));
+        assert_eq!(Checksum::try_from("BECH32"), Ok(Checksum::Bech32));
+        assert_eq!(Checksum::try_from("Codex32Long"), Ok(Checksum::Codex32Long));
+        assert_eq!(
+            Checksum::try_from("bech32m"),
+            Err(Error::UnknownChecksum {
+                name: "bech32m".into()
+            })
+        );
+    }
 }
@@ -14,25 +14,131 @@
 
 //! Volvelle Website (rust-wasm support code)
 
-mod checksum_worksheet;
-mod error;
-mod fe;
+pub mod checksum_worksheet;
+pub mod error;
+pub mod fe;
 
+use crate::checksum_worksheet::{
+    Action, CreateMode, GlobalResidueStatus, Layout, ShareDataDiff, ShareSummary,
+};
 use crate::error::Error;
-use crate::fe::Checksum;
+use crate::fe::{Checksum, Fe, Poly};
+use serde::Serialize;
+use std::cell::Cell;
 use wasm_bindgen::prelude::*;
 
-/// The entire checksumming session
+/// Version of the format output by `Session::local_storage_str`
+///
+/// Must be bumped whenever the format changes incompatibly.
+const STORAGE_FORMAT_VERSION: u32 = 3;
+
+/// The version of this build, for inclusion in bug reports
+///
+/// This is the crate version, with the local storage format version appended as
+/// semver build metadata, e.g. `0.1.0+storage.1`.
+#[wasm_bindgen]
+pub fn version() -> String {
+    format!(
+        "{}+storage.{}",
+        env!("CARGO_PKG_VERSION"),
+        STORAGE_FORMAT_VERSION
+    )
+}
+
+/// The shape of a session, without any share data, for `Session::summary_json`
+#[derive(Serialize)]
+struct SessionSummary {
+    version: String,
+    checksum: String,
+    size: usize,
+    threshold: usize,
+    n_shares: usize,
+    shares: Vec<ShareStats>,
+}
+
+/// The status of a single share, without any share data
+#[derive(Serialize)]
+struct ShareStats {
+    mode: &'static str,
+    completion: f64,
+    complete: bool,
+    valid: bool,
+}
+
+/// Read-only view of a share in a session, for the frontend's share list
 #[wasm_bindgen]
 #[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ShareInfo {
+    /// The index of the share in the session
+    pub idx: usize,
+    header: String,
+    /// Whether every share data character has been filled in
+    pub complete: bool,
+    /// Whether the share is complete and has a valid checksum
+    pub valid: bool,
+}
+
+#[wasm_bindgen]
+impl ShareInfo {
+    #[wasm_bindgen(getter)]
+    pub fn header(&self) -> String {
+        self.header.clone()
+    }
+}
+
+/// The entire checksumming session
+///
+/// Sessions compare equal regardless of whether they have been saved.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
 pub struct Session {
     hrp: String,
-    pub threshold: usize,
-    pub size: usize,
-    pub checksum: Checksum,
+    threshold: usize,
+    size: usize,
+    checksum: Checksum,
+    /// Whether `new_share` should fill in the threshold and a fresh share index
+    auto_header: bool,
+    /// Whether worksheet cells are rendered as decimal numbers rather than characters
+    numeric: bool,
+    /// How worksheet cells are arranged by `get_checksum_worksheet_cells`
+    layout: Layout,
+    /// Whether lowercase input is uppercased with a flash, rather than silently
+    flash_case: bool,
+    /// The most shares `new_share` will create, to stop a runaway frontend
+    share_limit: usize,
     shares: Vec<checksum_worksheet::Worksheet>,
+    /// Whether inputs are being appended to `input_log`
+    recording: bool,
+    /// Every `(id, val)` pair passed to `handle_input_change` while recording
+    input_log: Vec<(String, String)>,
+    /// Share ids below this may belong to removed shares. They are not reused, so
+    /// stale events for removed shares can be recognized. Not persisted, so not
+    /// compared.
+    retired_share_ids: usize,
+    /// Whether the session has changed since it was last serialized by
+    /// `local_storage_str`, which only has `&self`
+    dirty: Cell<bool>,
+}
+
+impl PartialEq for Session {
+    fn eq(&self, other: &Session) -> bool {
+        self.hrp == other.hrp
+            && self.threshold == other.threshold
+            && self.size == other.size
+            && self.checksum == other.checksum
+            && self.auto_header == other.auto_header
+            && self.numeric == other.numeric
+            && self.layout == other.layout
+            && self.flash_case == other.flash_case
+            && self.share_limit == other.share_limit
+            && self.shares == other.shares
+            && self.recording == other.recording
+            && self.input_log == other.input_log
+    }
 }
 
+impl Eq for Session {}
+
 #[wasm_bindgen]
 impl Session {
     #[wasm_bindgen(constructor)]
@@ -42,10 +148,54 @@ impl Session {
             threshold,
             size,
             checksum,
+            auto_header: false,
+            numeric: false,
+            layout: Layout::Diagonal,
+            flash_case: true,
+            share_limit: DEFAULT_SHARE_LIMIT,
             shares: vec![],
+            recording: false,
+            input_log: vec![],
+            retired_share_ids: 0,
+            dirty: Cell::new(false),
         }
     }
 
+    /// Constructs an empty session with room for the given number of shares
+    pub fn with_capacity(
+        hrp: String,
+        threshold: usize,
+        size: usize,
+        checksum: Checksum,
+        n_shares: usize,
+    ) -> Session {
+        let mut ret = Session::new(hrp, threshold, size, checksum);
+        ret.shares.reserve(n_shares);
+        ret
+    }
+
+    /// Constructs a session from a single complete share, loaded in verify mode
+    ///
+    /// The HRP, size and threshold are all taken from the share itself. If the
+    /// share has no threshold digit in its header, the threshold is set to 0.
+    pub fn from_share_string(share: &str, checksum: Checksum) -> Result<Session, JsError> {
+        Session::from_share_string_inner(share, checksum).map_err(From::from)
+    }
+
+    /// Constructs a session whose first share is a freshly generated random secret
+    ///
+    /// The secret share has index `S` and a random identifier, and is in create
+    /// mode with its checksum computed.
+    #[cfg(feature = "rand")]
+    pub fn new_random_secret(
+        hrp: String,
+        threshold: usize,
+        size: usize,
+        checksum: Checksum,
+    ) -> Result<Session, JsError> {
+        Session::new_random_secret_inner(hrp, threshold, size, checksum).map_err(From::from)
+    }
+
     #[wasm_bindgen(getter)]
     pub fn hrp(&self) -> String {
         self.hrp.clone()
@@ -53,18 +203,361 @@ impl Session {
     #[wasm_bindgen(setter)]
     pub fn set_hrp(&mut self, s: String) {
         self.hrp = s;
+        self.dirty.set(true);
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_threshold(&mut self, threshold: usize) {
+        self.threshold = threshold;
+        self.dirty.set(true);
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn checksum(&self) -> Checksum {
+        self.checksum
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_checksum(&mut self, checksum: Checksum) {
+        self.checksum = checksum;
+        self.dirty.set(true);
+    }
+
+    /// Whether `new_share` fills in the threshold and a fresh share index
+    #[wasm_bindgen(getter)]
+    pub fn auto_header(&self) -> bool {
+        self.auto_header
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_auto_header(&mut self, auto_header: bool) {
+        self.auto_header = auto_header;
+        self.dirty.set(true);
+    }
+
+    /// Whether worksheet cells are rendered as decimal numbers rather than characters
+    #[wasm_bindgen(getter)]
+    pub fn numeric(&self) -> bool {
+        self.numeric
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_numeric(&mut self, numeric: bool) {
+        self.numeric = numeric;
+        self.dirty.set(true);
+    }
+
+    /// How worksheet cells are arranged by `get_checksum_worksheet_cells`
+    #[wasm_bindgen(getter)]
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+        self.dirty.set(true);
+    }
+
+    /// Whether lowercase input is uppercased with a flash, rather than silently
+    #[wasm_bindgen(getter)]
+    pub fn flash_case(&self) -> bool {
+        self.flash_case
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_flash_case(&mut self, flash_case: bool) {
+        self.flash_case = flash_case;
+        self.dirty.set(true);
+    }
+
+    /// The most shares `new_share` will create
+    #[wasm_bindgen(getter)]
+    pub fn share_limit(&self) -> usize {
+        self.share_limit
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_share_limit(&mut self, share_limit: usize) {
+        self.share_limit = share_limit;
+        self.dirty.set(true);
+    }
+
+    /// Whether anything has changed since the last call to `local_storage_str` or
+    /// `update_from_local_storage_str`, e.g. to warn about unsaved changes
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.get()
+    }
+
+    /// Whether the HRP looks like a whole share was entered in its place
+    ///
+    /// This is true if the HRP contains the separator character or is implausibly
+    /// long, and lets the UI warn before a worksheet fails to construct.
+    pub fn looks_like_share_in_hrp(&self) -> bool {
+        self.hrp.contains(self.checksum.separator_char()) || self.hrp.len() > MAX_PLAUSIBLE_HRP_LEN
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Changes the size of the session's shares, reflowing any existing shares
+    ///
+    /// Errors, leaving the session unchanged, if the size is not possible for the
+    /// session's HRP and checksum.
+    pub fn set_size(&mut self, size: usize) -> Result<(), JsError> {
+        self.set_size_inner(size).map_err(From::from)
     }
 
     pub fn n_shares(&self) -> usize {
         self.shares.len()
     }
 
+    /// How many character errors the session's checksum can correct, for display
+    pub fn error_correction_capacity(&self) -> usize {
+        self.checksum.error_correction_capacity()
+    }
+
+    /// How many character errors the session's checksum is guaranteed to detect, e.g.
+    /// to show how strong a valid share's checksum match is
+    pub fn error_detection_capacity(&self) -> usize {
+        self.checksum.error_detection_capacity()
+    }
+
+    /// Whether the session has no shares
+    pub fn is_empty(&self) -> bool {
+        self.shares.is_empty()
+    }
+
+    /// Wipes and drops every share, e.g. to start over
+    ///
+    /// Also clears the input log, which contains share data.
+    pub fn clear_all(&mut self) {
+        for share in &mut self.shares {
+            share.wipe();
+        }
+        self.retired_share_ids = self.next_share_id();
+        self.shares.clear();
+        for (id, val) in &mut self.input_log {
+            id.clear();
+            val.clear();
+        }
+        self.input_log.clear();
+        self.dirty.set(true);
+    }
+
+    /// Number of additional complete shares needed before the secret can be recovered
+    ///
+    /// With threshold 0 the single share is the secret, so one share is needed.
+    pub fn shares_needed_for_recovery(&self) -> usize {
+        let n_complete = self.shares.iter().filter(|s| s.is_complete()).count();
+        self.threshold.max(1).saturating_sub(n_complete)
+    }
+
     /// Adds a share to a session
+    ///
+    /// If `auto_header` is set and the checksum has a codex32 header, fills in the
+    /// threshold digit and the first share index not used by any other share. With
+    /// threshold 0 the secret is not split, so only one share can be created this way.
     pub fn new_share(&mut self) -> Result<usize, JsError> {
-        let idx = self.shares.len();
-        let new = checksum_worksheet::Worksheet::new(&self.hrp, self.size, self.checksum, idx)?;
+        self.new_share_inner().map_err(From::from)
+    }
+
+    /// Removes a share, moving every later share down by one
+    ///
+    /// Shares keep their cells' DOM ids, so the removed share's ids are never reused.
+    pub fn remove_share(&mut self, idx: usize) -> Result<(), JsError> {
+        self.remove_share_inner(idx).map_err(From::from)
+    }
+
+    /// Copies the session for independent verification of its shares
+    ///
+    /// Every share is switched to verify mode, keeping only its share data. The
+    /// computed cells are blank until recomputed with `recompute_share`.
+    pub fn clone_for_verification(&self) -> Session {
+        let mut ret = self.clone();
+        for share in &mut ret.shares {
+            share.set_mode(CreateMode::Verify);
+        }
+        ret
+    }
+
+    /// Recomputes every computed cell of a share from its inputs
+    ///
+    /// Returns a list of updated cells for the JS to update the DOM with
+    pub fn recompute_share(&mut self, idx: usize) -> Result<js_sys::Array, JsError> {
+        let share = self
+            .shares
+            .get_mut(idx)
+            .ok_or_else(|| JsError::new("recompute_share: bad share idx"))?;
+        let ret: js_sys::Array = share
+            .recompute_all()
+            .into_iter()
+            .map(JsValue::from)
+            .collect();
+        self.dirty.set(true);
+        Ok(ret)
+    }
+
+    /// Gets the mode of a share
+    pub fn share_mode(&self, idx: usize) -> Result<CreateMode, JsError> {
+        let share = self
+            .shares
+            .get(idx)
+            .ok_or_else(|| JsError::new("share_mode: bad share idx"))?;
+        Ok(share.mode())
+    }
+
+    /// Gets the checksum used by a share
+    ///
+    /// Currently this is always the session's checksum.
+    pub fn share_checksum(&self, idx: usize) -> Result<Checksum, JsError> {
+        let share = self
+            .shares
+            .get(idx)
+            .ok_or_else(|| JsError::new("share_checksum: bad share idx"))?;
+        Ok(share.checksum())
+    }
+
+    /// Gets the status of a share's global residue row
+    pub fn global_residue_status(&self, idx: usize) -> Result<GlobalResidueStatus, JsError> {
+        let share = self
+            .shares
+            .get(idx)
+            .ok_or_else(|| JsError::new("global_residue_status: bad share idx"))?;
+        Ok(share.global_residue_status())
+    }
+
+    /// Switches a share between create and verify mode
+    ///
+    /// All computed cells are blanked; call `recompute_share` to fill them back in.
+    pub fn set_share_mode(&mut self, idx: usize, mode: CreateMode) -> Result<(), JsError> {
+        let share = self
+            .shares
+            .get_mut(idx)
+            .ok_or_else(|| JsError::new("set_share_mode: bad share idx"))?;
+        share.set_mode(mode);
+        self.dirty.set(true);
+        Ok(())
+    }
+
+    /// Gets a summary of the status of a share
+    pub fn share_summary(&self, idx: usize) -> Result<ShareSummary, JsError> {
+        let share = self
+            .shares
+            .get(idx)
+            .ok_or_else(|| JsError::new("share_summary: bad share idx"))?;
+        Ok(share.summary())
+    }
+
+    /// Gets a summary of the status of every share
+    pub fn all_summaries(&self) -> js_sys::Array {
+        self.shares
+            .iter()
+            .map(|share| JsValue::from(share.summary()))
+            .collect()
+    }
+
+    /// Gets the index, header and status of every share, in one call
+    pub fn shares_info(&self) -> js_sys::Array {
+        self.shares_info_inner()
+            .into_iter()
+            .map(JsValue::from)
+            .collect()
+    }
+
+    /// Exports every share as a string, for the user to back up
+    ///
+    /// Errors with the index of the first incomplete share, if any.
+    pub fn export_all_shares(&self) -> Result<js_sys::Array, JsError> {
+        Ok(self
+            .share_strings()?
+            .into_iter()
+            .map(JsValue::from)
+            .collect())
+    }
+
+    /// Imports a complete share, returning its index
+    ///
+    /// The share must match the session's HRP, size and checksum, and be valid.
+    pub fn import_share(&mut self, share: &str) -> Result<usize, JsError> {
+        let new = self.parse_share(share, self.next_share_id())?;
         self.shares.push(new);
-        Ok(idx)
+        self.dirty.set(true);
+        Ok(self.shares.len() - 1)
+    }
+
+    /// Replaces a share in place with a complete share, e.g. a corrected copy
+    ///
+    /// The share keeps its index, so no other share is renumbered. The new share
+    /// is checked as by `import_share`.
+    pub fn replace_share(&mut self, idx: usize, share: &str) -> Result<(), JsError> {
+        self.replace_share_inner(idx, share).map_err(From::from)
+    }
+
+    /// Imports a set of complete shares, e.g. from a backup
+    ///
+    /// If any share fails to import, none are imported.
+    pub fn import_all_shares(&mut self, shares: Vec<String>) -> Result<(), JsError> {
+        self.import_all_shares_inner(&shares).map_err(From::from)
+    }
+
+    /// The identifier shared by every complete share, if there are any
+    ///
+    /// Errors with the index of the first share whose identifier differs, which
+    /// indicates shares from different secrets have been mixed together.
+    pub fn common_identifier(&self) -> Result<Option<String>, JsError> {
+        self.common_identifier_inner().map_err(From::from)
+    }
+
+    /// Checks that the valid shares can be used to recover the secret
+    ///
+    /// Errors if two valid shares have the same index, if the secret share `S`
+    /// is among them (unless the threshold is 0), or if there are fewer than
+    /// `threshold` of them.
+    pub fn can_recover(&self) -> Result<(), JsError> {
+        self.can_recover_inner().map_err(From::from)
+    }
+
+    /// Recovers the secret share from the first `threshold` valid shares
+    ///
+    /// With threshold 0 the first valid share is the secret, and is returned as-is.
+    pub fn recover_secret(&self) -> Result<String, JsError> {
+        self.recover_secret_inner().map_err(From::from)
+    }
+
+    /// Shows the working of `recover_secret` as a table
+    ///
+    /// Each row is one of the shares used for recovery, multiplied by its Lagrange
+    /// coefficient; adding up the rows column-wise gives the secret share.
+    pub fn recovery_matrix(&self) -> Result<js_sys::Array, JsError> {
+        Ok(self
+            .recovery_matrix_inner()?
+            .into_iter()
+            .map(|row| JsValue::from(row.into_iter().collect::<String>()))
+            .collect())
+    }
+
+    /// Compares the share data of two shares, e.g. to see why two copies of the
+    /// same share differ
+    ///
+    /// Returns a `[position, a, b]` array for each share data position where the
+    /// shares differ, with `null` for blank cells.
+    pub fn compare_shares(&self, a: usize, b: usize) -> Result<js_sys::Array, JsError> {
+        let opt_js = |ch: Option<char>| ch.map_or(JsValue::NULL, |ch| ch.to_string().into());
+        Ok(self
+            .compare_shares_inner(a, b)?
+            .into_iter()
+            .map(|(pos, a, b)| {
+                JsValue::from(js_sys::Array::of3(&pos.into(), &opt_js(a), &opt_js(b)))
+            })
+            .collect())
+    }
+
+    /// The threshold encoded in the header of every complete share, if there are any
+    ///
+    /// Errors with the index of the first share whose threshold differs.
+    pub fn infer_threshold(&self) -> Result<Option<usize>, JsError> {
+        self.infer_threshold_inner().map_err(From::from)
     }
 
     /// Gets the list of cells to build a checksum worksheet from
@@ -77,10 +570,22 @@ impl Session {
         // if we directly create a js_sys::Array in get_dom_cells then our unit
         // tests break
         share
-            .get_dom_cells()
+            .get_dom_cells_with_layout(self.numeric, self.layout)
             .map(|vec| vec.into_iter().map(JsValue::from).collect())
     }
 
+    /// Exports every cell of a share's worksheet as CSV, for use in a spreadsheet
+    ///
+    /// Each line after the header is `row,col,type,value`, with `type` as in
+    /// `get_checksum_worksheet_cells`.
+    pub fn worksheet_csv(&self, idx: usize) -> Result<String, JsError> {
+        let share = self
+            .shares
+            .get(idx)
+            .ok_or_else(|| JsError::new("worksheet_csv: bad share idx"))?;
+        Ok(share.to_csv())
+    }
+
     /// Gets the list of cells to build a checksum worksheet from
     pub fn get_checksum_worksheet_header_str(&self, idx: usize) -> Result<String, JsError> {
         let share = self
@@ -92,45 +597,108 @@ impl Session {
 
     /// Extracts the share index from an input ID
     pub fn get_idx_of(&self, id: &str) -> Result<usize, JsError> {
-        Ok(cell_from_name(id)?[0])
+        Ok(self.share_position(cell_from_name(id)?[0])?)
     }
 
     /// Responds to a user update of a cell by updating the state of the sheet
     ///
     /// Returns a list of updated cells for the JS to update the DOM with
     pub fn handle_input_change(&mut self, id: &str, val: &str) -> Result<js_sys::Array, JsError> {
-        let id = cell_from_name(id)?;
-
-        let share = self
-            .shares
-            .get_mut(id[0])
-            .ok_or_else(|| JsError::new("handle_input_change: bad active share idx"))?;
         // FIXME this conversion is inefficient and ought to be unnecessary but
         // if we directly create a js_sys::Array in get_dom_cells then our unit
         // tests break
-        share
-            .handle_input_change(id[1], id[2], val)
+        self.apply_input(id, val)
+            .map(|vec| vec.into_iter().map(JsValue::from).collect())
+            .map_err(From::from)
+    }
+
+    /// Handles several inputs at once, given as parallel lists of cell ids and values
+    ///
+    /// Returns a single list of actions, with only the final update to each cell.
+    pub fn handle_input_batch(
+        &mut self,
+        ids: Vec<String>,
+        vals: Vec<String>,
+    ) -> Result<js_sys::Array, JsError> {
+        if ids.len() != vals.len() {
+            return Err(JsError::new("handle_input_batch: mismatched ids and vals"));
+        }
+        let changes: Vec<_> = ids.into_iter().zip(vals).collect();
+        self.apply_input_batch(&changes)
             .map(|vec| vec.into_iter().map(JsValue::from).collect())
             .map_err(From::from)
     }
 
+    /// Turns recording of inputs into the input log on or off
+    ///
+    /// Turning recording off does not clear the log.
+    pub fn record_inputs(&mut self, on: bool) {
+        self.recording = on;
+    }
+
+    /// Outputs a JSON description of the session for bug reports
+    ///
+    /// This includes the session parameters and the status of each share, but
+    /// none of the share data itself, so it is safe to share.
+    pub fn summary_json(&self) -> String {
+        let summary = SessionSummary {
+            version: version(),
+            checksum: format!("{:?}", self.checksum),
+            size: self.size,
+            threshold: self.threshold,
+            n_shares: self.shares.len(),
+            shares: self
+                .shares
+                .iter()
+                .map(|share| {
+                    let summary = share.summary();
+                    ShareStats {
+                        mode: match summary.mode {
+                            CreateMode::Create => "create",
+                            CreateMode::Verify => "verify",
+                            CreateMode::Blank => "blank",
+                        },
+                        completion: summary.completion,
+                        complete: share.is_complete(),
+                        valid: summary.valid,
+                    }
+                })
+                .collect(),
+        };
+        serde_json::to_string(&summary).expect("summary contains no maps or non-finite floats")
+    }
+
+    /// Outputs the recorded input log as a JSON array of `[id, val]` pairs
+    pub fn input_log(&self) -> Result<String, JsError> {
+        serde_json::to_string(&self.input_log).map_err(From::from)
+    }
+
+    /// Re-applies every input from a log output by `input_log`
+    pub fn replay(&mut self, log: &str) -> Result<(), JsError> {
+        self.replay_inner(log).map_err(From::from)
+    }
+
     /// Outputs a serialization of the session which can be read from local storage
     pub fn local_storage_str(&self) -> String {
         let mut ret = format!(
             "{}_{}_{}_{}_{}",
             self.size,
-            match self.checksum {
-                Checksum::Bech32 => 0,
-                Checksum::Codex32 => 1,
-            },
+            self.checksum.to_storage_u8(),
             self.threshold,
             self.hrp.len(),
             self.hrp,
         );
         for s in &self.shares {
             ret.push('_');
+            ret.push(match s.mode() {
+                CreateMode::Create => '0',
+                CreateMode::Verify => '1',
+                CreateMode::Blank => '2',
+            });
+            ret.push(':');
             s.cells_into_str(&mut ret);
         }
+        self.dirty.set(false);
         ret
     }
 
@@ -144,12 +712,9 @@ impl Session {
         let checksum = iter
             .next()
             .ok_or_else(|| JsError::new("missing checksum"))?
-            .parse::<usize>()?;
-        self.checksum = match checksum {
-            0 => Checksum::Bech32,
-            1 => Checksum::Codex32,
-            _ => return Err(JsError::new("bad checksum value")),
-        };
+            .parse::<u8>()?;
+        self.checksum = Checksum::from_storage_u8(checksum)
+            .ok_or_else(|| JsError::new("bad checksum value"))?;
         self.threshold = iter
             .next()
             .ok_or_else(|| JsError::new("missing threshold"))?
@@ -165,59 +730,1760 @@ impl Session {
         self.hrp = rem[..hrp_len].into();
 
         if rem.len() > hrp_len {
-            for share_data in rem[hrp_len + 1..].split("_") {
-                let idx = self.new_share()?;
-                self.shares[idx].cells_from_str(share_data)?;
-            }
+            self.load_shares(&rem[hrp_len + 1..])?;
         }
+        self.dirty.set(false);
         Ok(())
     }
 }
 
-/// Helper function to translate a cell ID into a shareidx/row/cell index tuple
-fn cell_from_name(s: &str) -> Result<[usize; 3], Error> {
-    use std::str::FromStr;
-    let mut iter = s.split('_');
-    if iter.next() != Some("inp") {
-        return Err(Error::UnknownCell {
-            id: s.into(),
-            reason: "no inp_ prefix",
-        });
+impl Session {
+    fn set_size_inner(&mut self, size: usize) -> Result<(), Error> {
+        // Check the size even if there are no shares to reflow
+        checksum_worksheet::Worksheet::new(&self.hrp, size, self.checksum, 0)?;
+        let resized = self
+            .shares
+            .iter()
+            .map(|share| share.resize(size))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.shares = resized;
+        self.size = size;
+        self.dirty.set(true);
+        Ok(())
     }
-    let mut ret = [0; 3];
-    for i in 0..3 {
-        let ns = iter.next().ok_or_else(|| Error::UnknownCell {
-            id: s.into(),
-            reason: "missing number",
-        })?;
-        ret[i] = usize::from_str(ns).map_err(|_| Error::UnknownCell {
-            id: s.into(),
-            reason: "bad number",
-        })?;
+
+    /// Creates a new share, returning its index
+    fn new_share_inner(&mut self) -> Result<usize, Error> {
+        let idx = self.shares.len();
+        let auto_header = self.auto_header && self.checksum.has_codex32_header();
+        if auto_header && self.threshold == 0 && idx > 0 {
+            return Err(Error::UnsharedSecret);
+        }
+        if idx >= self.share_limit {
+            return Err(Error::TooManyShares {
+                limit: self.share_limit,
+            });
+        }
+        let mut new = checksum_worksheet::Worksheet::new(
+            &self.hrp,
+            self.size,
+            self.checksum,
+            self.next_share_id(),
+        )?;
+        if auto_header {
+            // Threshold 0 means the only share is the secret itself
+            let threshold = std::char::from_digit(self.threshold as u32, 10)
+                .filter(|ch| Fe::try_from(*ch).is_ok());
+            let index = if self.threshold == 0 {
+                Some('S')
+            } else {
+                SHARE_INDICES.chars().find(|&ch| {
+                    let fe = Fe::try_from(ch).ok();
+                    self.shares.iter().all(|share| share.share_index() != fe)
+                })
+            };
+            if let Some(ch) = threshold {
+                new.set_data_char(0, ch)?;
+            }
+            if let Some(ch) = index {
+                new.set_data_char(5, ch)?;
+            }
+        }
+        self.shares.push(new);
+        self.dirty.set(true);
+        Ok(idx)
     }
-    if iter.next().is_none() {
-        Ok(ret)
-    } else {
-        Err(Error::UnknownCell {
-            id: s.into(),
-            reason: "extra numbers",
-        })
+
+    fn remove_share_inner(&mut self, idx: usize) -> Result<(), Error> {
+        if idx >= self.shares.len() {
+            return Err(self.invalid_share(idx));
+        }
+        self.retired_share_ids = self.next_share_id();
+        self.shares.remove(idx).wipe();
+        self.dirty.set(true);
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    fn replace_share_inner(&mut self, idx: usize, share: &str) -> Result<(), Error> {
+        if idx >= self.shares.len() {
+            return Err(self.invalid_share(idx));
+        }
+        let new = self.parse_share(share, self.shares[idx].idx())?;
+        std::mem::replace(&mut self.shares[idx], new).wipe();
+        self.dirty.set(true);
+        Ok(())
+    }
 
-    #[test]
-    fn test_cell_from_name() {
-        assert_eq!(cell_from_name("inp_0_0_0"), Ok([0, 0, 0]));
-        assert_eq!(cell_from_name("inp_0_10_0"), Ok([0, 10, 0]));
-        assert_eq!(cell_from_name("inp_100_10_10"), Ok([100, 10, 10]));
-        assert!(cell_from_name("inp_10_10_10_10").is_err());
-        assert!(cell_from_name("inp_10_10").is_err());
-        assert!(cell_from_name("inP_10_10_10").is_err());
-        assert!(cell_from_name("inp_10").is_err());
+    /// The id for a new share, which is not used by any current or removed share
+    fn next_share_id(&self) -> usize {
+        self.shares
+            .iter()
+            .map(|share| share.idx() + 1)
+            .fold(self.retired_share_ids, usize::max)
+    }
+
+    /// The index in `shares` of the share with the given id, as used in DOM ids
+    fn share_position(&self, id: usize) -> Result<usize, Error> {
+        self.shares
+            .iter()
+            .position(|share| share.idx() == id)
+            .ok_or_else(|| self.bad_share_id(id))
+    }
+
+    /// The error for an event targeting a share id which does not exist
+    ///
+    /// Distinguishes ids of shares which have since been removed, e.g. from a
+    /// stale DOM event, from ids which were never valid.
+    fn bad_share_id(&self, id: usize) -> Error {
+        if id < self.next_share_id() {
+            Error::RemovedShare { idx: id }
+        } else {
+            self.invalid_share(id)
+        }
+    }
+
+    /// The error for a share index past the end of `shares`
+    fn invalid_share(&self, idx: usize) -> Error {
+        Error::InvalidShare {
+            idx,
+            n_shares: self.shares.len(),
+        }
+    }
+
+    /// Loads the `_`-separated shares from a local storage string
+    ///
+    /// On failure, reports which share could not be loaded.
+    fn load_shares(&mut self, s: &str) -> Result<(), Error> {
+        self.shares.reserve(s.split('_').count());
+        for (n, share_data) in s.split('_').enumerate() {
+            self.load_share(share_data)
+                .map_err(|reason| Error::CorruptShare {
+                    idx: n,
+                    data: share_data.into(),
+                    reason: reason.to_string(),
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Loads a single share from its local storage representation
+    fn load_share(&mut self, share_data: &str) -> Result<(), Error> {
+        // Shares saved before modes were introduced have no mode prefix
+        let (mode, share_data) = match share_data.split_once(':') {
+            Some(("0", data)) => (CreateMode::Create, data),
+            Some(("1", data)) => (CreateMode::Verify, data),
+            Some(("2", data)) => (CreateMode::Blank, data),
+            Some((mode, _)) => return Err(Error::BadShareMode { mode: mode.into() }),
+            None => (CreateMode::Create, share_data),
+        };
+        // The header is loaded along with everything else, so don't fill it in
+        // (and don't apply `new_share`'s restrictions on which shares may exist)
+        let mut share = checksum_worksheet::Worksheet::new(
+            &self.hrp,
+            self.size,
+            self.checksum,
+            self.next_share_id(),
+        )?;
+        share.set_mode(mode);
+        share.cells_from_str(share_data)?;
+        self.shares.push(share);
+        Ok(())
+    }
+
+    /// Outputs every share as a string, or the index of the first incomplete share
+    fn share_strings(&self) -> Result<Vec<String>, Error> {
+        self.shares
+            .iter()
+            .enumerate()
+            .map(|(idx, share)| {
+                share
+                    .to_share_string()
+                    .ok_or(Error::IncompleteShare { idx })
+            })
+            .collect()
+    }
+
+    /// Parses a share string into a worksheet compatible with this session
+    ///
+    /// Whitespace and dashes used to group the share's characters are ignored.
+    fn parse_share(&self, share: &str, idx: usize) -> Result<checksum_worksheet::Worksheet, Error> {
+        let share = &self.checksum.normalize_share_string(share);
+        let hrp = share
+            .rfind(self.checksum.separator_char())
+            .map(|sep| &share[..sep])
+            .ok_or(Error::MissingSeparator)?;
+        if !hrp.eq_ignore_ascii_case(&self.hrp) {
+            return Err(Error::HrpMismatch {
+                expected: self.hrp.clone(),
+                actual: hrp.into(),
+            });
+        }
+        // With the HRP matching, a size mismatch is down to the data the user entered
+        if share.len() != self.size {
+            return Err(Error::BadShareDataLen {
+                len: share.len() - hrp.len() - 1,
+                expected: self.size.saturating_sub(hrp.len() + 1),
+            });
+        }
+        // Independently recompute the checksum, to report exactly which characters
+        // are wrong rather than just that the share is invalid
+        let fes: Vec<Fe> = Poly::from_share_string(share, self.checksum)?
+            .iter()
+            .collect();
+        let (data, provided) = fes.split_at(fes.len() - self.checksum.len());
+        let expected = self.checksum.compute_checksum(hrp, data);
+        let offset = share.len() - provided.len();
+        let positions: Vec<usize> = provided
+            .iter()
+            .zip(expected.iter())
+            .enumerate()
+            .filter(|(_, (a, b))| **a != *b)
+            .map(|(n, _)| offset + n)
+            .collect();
+        if !positions.is_empty() {
+            return Err(Error::ChecksumMismatch { positions });
+        }
+        checksum_worksheet::Worksheet::from_share_string(share, self.checksum, idx)
+    }
+
+    fn from_share_string_inner(share: &str, checksum: Checksum) -> Result<Session, Error> {
+        let share = &checksum.normalize_share_string(share);
+        let sep = share
+            .rfind(checksum.separator_char())
+            .ok_or(Error::MissingSeparator)?;
+        let worksheet = checksum_worksheet::Worksheet::from_share_string(share, checksum, 0)?;
+        let mut ret = Session::new(
+            share[..sep].to_ascii_lowercase(),
+            worksheet.threshold().unwrap_or(0),
+            share.len(),
+            checksum,
+        );
+        ret.shares.push(worksheet);
+        Ok(ret)
+    }
+
+    #[cfg(feature = "rand")]
+    fn new_random_secret_inner(
+        hrp: String,
+        threshold: usize,
+        size: usize,
+        checksum: Checksum,
+    ) -> Result<Session, Error> {
+        let mut ret = Session::new(hrp, threshold, size, checksum);
+        let mut secret = checksum_worksheet::Worksheet::new(&ret.hrp, size, checksum, 0)?;
+
+        let threshold_ch = std::char::from_digit(threshold as u32, 10)
+            .filter(|ch| Fe::try_from(*ch).is_ok())
+            .ok_or(Error::BadThreshold { threshold })?;
+        let identifier: String = (0..4).map(|_| char::from(Fe::random())).collect();
+        secret.fill_header(threshold_ch, &identifier, 'S')?;
+        // In create mode, filling in the last data character computes the checksum
+        let n_data = size - ret.hrp.len() - 1 - checksum.len();
+        for pos in checksum.header_len()..n_data {
+            secret.set_data_char(pos, Fe::random().into())?;
+        }
+
+        ret.shares.push(secret);
+        Ok(ret)
+    }
+
+    /// Imports a set of complete shares, leaving the session unchanged on error
+    fn import_all_shares_inner(&mut self, shares: &[String]) -> Result<(), Error> {
+        let first_id = self.next_share_id();
+        let new = shares
+            .iter()
+            .enumerate()
+            .map(|(n, share)| self.parse_share(share, first_id + n))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.shares.extend(new);
+        self.dirty.set(true);
+        Ok(())
+    }
+
+    /// The threshold shared by every complete share, or the first mismatching share
+    fn infer_threshold_inner(&self) -> Result<Option<usize>, Error> {
+        let mut ret = None;
+        for (idx, share) in self.shares.iter().enumerate() {
+            if !share.is_complete() {
+                continue;
+            }
+            let threshold = match share.threshold() {
+                Some(threshold) => threshold,
+                None => continue,
+            };
+            match ret {
+                Some(expected) if expected != threshold => {
+                    return Err(Error::ThresholdMismatch {
+                        idx,
+                        expected,
+                        actual: threshold,
+                    })
+                }
+                Some(_) => {}
+                None => ret = Some(threshold),
+            }
+        }
+        Ok(ret)
+    }
+
+    /// The identifier shared by every complete share, or the first mismatching share
+    fn common_identifier_inner(&self) -> Result<Option<String>, Error> {
+        let mut ret: Option<String> = None;
+        for (idx, share) in self.shares.iter().enumerate() {
+            if !share.is_complete() {
+                continue;
+            }
+            let id = share.identifier().unwrap();
+            match ret {
+                Some(ref expected) if *expected != id => {
+                    return Err(Error::IdentifierMismatch {
+                        idx,
+                        expected: expected.clone(),
+                        actual: id,
+                    })
+                }
+                Some(_) => {}
+                None => ret = Some(id),
+            }
+        }
+        Ok(ret)
+    }
+
+    /// The share data of the first `threshold` valid shares with distinct indices, each
+    /// scaled by its Lagrange coefficient for recovering the secret
+    fn recovery_rows(&self) -> Result<Vec<Vec<Fe>>, Error> {
+        let needed = self.threshold.max(1);
+        let mut indices = Vec::with_capacity(needed);
+        let mut data = Vec::with_capacity(needed);
+        for share in self.shares.iter().filter(|share| share.is_valid()) {
+            let index = share.share_index().unwrap();
+            if !indices.contains(&index) && indices.len() < needed {
+                indices.push(index);
+                data.push(share.share_data_fes().unwrap());
+            }
+        }
+        if indices.len() < needed {
+            return Err(Error::NotEnoughShares {
+                needed,
+                have: indices.len(),
+            });
+        }
+
+        let secret_index = Fe::try_from('S').unwrap();
+        let coeffs =
+            fe::lagrange_coefficients(&indices, secret_index).expect("indices are distinct");
+        Ok(data
+            .into_iter()
+            .zip(coeffs)
+            .map(|(row, coeff)| row.into_iter().map(|fe| coeff * fe).collect())
+            .collect())
+    }
+
+    fn can_recover_inner(&self) -> Result<(), Error> {
+        let secret_index = Fe::try_from('S').unwrap();
+        let mut indices = vec![];
+        for share in self.shares.iter().filter(|share| share.is_valid()) {
+            let index = share.share_index().unwrap();
+            if indices.contains(&index) {
+                return Err(Error::DuplicateShareIndex {
+                    index: index.into(),
+                });
+            }
+            if index == secret_index && self.threshold > 0 {
+                return Err(Error::SecretShareProvided);
+            }
+            indices.push(index);
+        }
+        let needed = self.threshold.max(1);
+        if indices.len() < needed {
+            return Err(Error::NotEnoughShares {
+                needed,
+                have: indices.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Recovers the secret share as a string
+    fn recover_secret_inner(&self) -> Result<String, Error> {
+        if self.threshold == 0 {
+            return self
+                .shares
+                .iter()
+                .find(|share| share.is_valid())
+                .and_then(|share| share.to_share_string())
+                .ok_or(Error::NotEnoughShares { needed: 1, have: 0 });
+        }
+        let rows = self.recovery_rows()?;
+        let mut ret = format!("{}1", self.hrp.to_ascii_uppercase());
+        for pos in 0..rows[0].len() {
+            let sum = rows.iter().fold(Fe::zero(), |acc, row| acc + row[pos]);
+            ret.push(sum.into());
+        }
+        Ok(ret)
+    }
+
+    fn shares_info_inner(&self) -> Vec<ShareInfo> {
+        self.shares
+            .iter()
+            .enumerate()
+            .map(|(idx, share)| ShareInfo {
+                idx,
+                header: share.header_str(),
+                complete: share.is_complete(),
+                valid: share.is_valid(),
+            })
+            .collect()
+    }
+
+    /// The share data positions where two shares differ, with both values
+    fn compare_shares_inner(&self, a: usize, b: usize) -> Result<Vec<ShareDataDiff>, Error> {
+        let share_a = self.shares.get(a).ok_or_else(|| self.invalid_share(a))?;
+        let share_b = self.shares.get(b).ok_or_else(|| self.invalid_share(b))?;
+        Ok(share_a.compare_share_data(share_b))
+    }
+
+    /// The recovery rows as bech32 characters
+    fn recovery_matrix_inner(&self) -> Result<Vec<Vec<char>>, Error> {
+        Ok(self
+            .recovery_rows()?
+            .into_iter()
+            .map(|row| row.into_iter().map(char::from).collect())
+            .collect())
+    }
+
+    /// Applies an input to the appropriate worksheet
+    fn apply_input(&mut self, id: &str, val: &str) -> Result<Vec<Action>, Error> {
+        if self.recording {
+            self.input_log.push((id.into(), val.into()));
+        }
+        let cell = cell_from_name(id)?;
+        let idx = self.share_position(cell[0])?;
+        let share = &mut self.shares[idx];
+        share.set_flash_case(self.flash_case);
+        let ret = share.handle_input_change(cell[1], cell[2], val)?;
+        self.dirty.set(true);
+        Ok(ret)
+    }
+
+    /// Applies a batch of inputs, propagating once per worksheet
+    ///
+    /// Every input is checked before any is applied, so on error no share changes.
+    fn apply_input_batch(&mut self, changes: &[(String, String)]) -> Result<Vec<Action>, Error> {
+        let mut by_share: Vec<Vec<(usize, usize, &str)>> = vec![vec![]; self.shares.len()];
+        for (id, val) in changes {
+            let cell = cell_from_name(id)?;
+            let idx = self.share_position(cell[0])?;
+            by_share[idx].push((cell[1], cell[2], val));
+        }
+        for (share, changes) in self.shares.iter().zip(&by_share) {
+            share.check_input_batch(changes)?;
+        }
+        if self.recording {
+            self.input_log.extend(changes.iter().cloned());
+        }
+
+        let mut ret = vec![];
+        for (share, changes) in self.shares.iter_mut().zip(by_share) {
+            if !changes.is_empty() {
+                share.set_flash_case(self.flash_case);
+                ret.extend(share.handle_input_batch(&changes)?);
+            }
+        }
+        self.dirty.set(true);
+        Ok(ret)
+    }
+
+    fn replay_inner(&mut self, log: &str) -> Result<(), Error> {
+        let log: Vec<(String, String)> =
+            serde_json::from_str(log).map_err(|e| Error::Json { msg: e.to_string() })?;
+        for (id, val) in log {
+            self.apply_input(&id, &val)?;
+        }
+        Ok(())
+    }
+}
+
+/// HRPs longer than this are assumed to be a pasted share, by `Session::looks_like_share_in_hrp`
+///
+/// Real HRPs are short, e.g. `ms` for codex32 and `bc` or `tb` for segwit addresses.
+const MAX_PLAUSIBLE_HRP_LEN: usize = 10;
+
+/// The default for `Session::share_limit`, the number of distinct bech32 characters
+/// and so the most shares that can have distinct indices
+const DEFAULT_SHARE_LIMIT: usize = 32;
+
+/// Share indices in the order they are assigned by `Session::new_share`; `S` is
+/// reserved for the secret
+const SHARE_INDICES: &str = "ACDEFGHJKLMNPQRTUVWXYZ023456789";
+
+/// Helper function to translate a cell ID into a shareidx/row/cell index tuple
+fn cell_from_name(s: &str) -> Result<[usize; 3], Error> {
+    use std::str::FromStr;
+    let mut iter = s.split('_');
+    if iter.next() != Some("inp") {
+        return Err(Error::UnknownCell {
+            id: s.into(),
+            reason: "no inp_ prefix",
+        });
+    }
+    let mut ret = [0; 3];
+    for n in &mut ret {
+        let ns = iter.next().ok_or_else(|| Error::UnknownCell {
+            id: s.into(),
+            reason: "missing number",
+        })?;
+        *n = usize::from_str(ns).map_err(|_| Error::UnknownCell {
+            id: s.into(),
+            reason: "bad number",
+        })?;
+    }
+    if iter.next().is_none() {
+        Ok(ret)
+    } else {
+        Err(Error::UnknownCell {
+            id: s.into(),
+            reason: "extra numbers",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// BIP-93 test vector share
+    const SHARE_2NAMEA: &str = "MS12NAMEA320ZYXWVUTSRQPNMLKJHGFEDCAXRPP870HKKQRM";
+    /// BIP-93 test vector share from the same secret
+    const SHARE_2NAMEC: &str = "MS12NAMECACDEFGHJKLMNPQRSTUVWXYZ023FTR2GDZMPY6PN";
+
+    /// Fills in all the non-checksum characters of a share from a share string
+    fn fill_share(session: &mut Session, idx: usize, share: &str) {
+        let n_data = session.size - session.hrp.len() - 1 - session.checksum.len();
+        let data = &share[session.hrp.len() + 1..];
+        for (pos, ch) in data.chars().take(n_data).enumerate() {
+            session.shares[idx].set_data_char(pos, ch).unwrap();
+        }
+    }
+
+    /// Builds a `Session` by replaying a scripted sequence of inputs
+    struct SessionBuilder {
+        session: Session,
+        inputs: Vec<(String, String)>,
+    }
+
+    impl SessionBuilder {
+        fn new(hrp: &str, threshold: usize, size: usize, checksum: Checksum) -> Self {
+            SessionBuilder {
+                session: Session::new(hrp.into(), threshold, size, checksum),
+                inputs: vec![],
+            }
+        }
+
+        fn shares(mut self, n: usize) -> Self {
+            for _ in 0..n {
+                self.session.new_share().unwrap();
+            }
+            self
+        }
+
+        /// Types each character of `s` into consecutive cells of a row, starting at `cidx`
+        fn type_str(mut self, share: usize, ridx: usize, cidx: usize, s: &str) -> Self {
+            for (n, ch) in s.chars().enumerate() {
+                let id = format!("inp_{}_{}_{}", share, ridx, cidx + n);
+                self.inputs.push((id, ch.to_string()));
+            }
+            self
+        }
+
+        fn build(mut self) -> Session {
+            for (id, val) in &self.inputs {
+                self.session.apply_input(id, val).unwrap();
+            }
+            self.session
+        }
+    }
+
+    /// A stable textual rendering of every share in a session, for whole-sheet comparison
+    fn snapshot(session: &Session) -> String {
+        let mut ret = String::new();
+        for (n, share) in session.shares.iter().enumerate() {
+            ret.push_str(&format!("share {}:\n", n));
+            ret.push_str(&share.as_ascii_art());
+            ret.push('\n');
+        }
+        ret
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn new_random_secret() {
+        for checksum in [Checksum::Codex32, Checksum::Codex32Long] {
+            let size = if checksum == Checksum::Codex32 {
+                48
+            } else {
+                126
+            };
+            let session = Session::new_random_secret_inner("ms".into(), 2, size, checksum).unwrap();
+            assert_eq!(session.n_shares(), 1);
+            let share = &session.shares[0];
+            assert!(share.is_valid());
+            assert_eq!(share.mode(), CreateMode::Create);
+            assert_eq!(share.threshold(), Some(2));
+            assert_eq!(share.share_index(), Fe::try_from('S').ok());
+            assert_eq!(share.to_share_string().unwrap().len(), size);
+        }
+
+        // Fresh randomness each time
+        let a = Session::new_random_secret_inner("ms".into(), 3, 48, Checksum::Codex32).unwrap();
+        let b = Session::new_random_secret_inner("ms".into(), 3, 48, Checksum::Codex32).unwrap();
+        assert_ne!(a.shares[0].to_share_string(), b.shares[0].to_share_string());
+
+        assert_eq!(
+            Session::new_random_secret_inner("ms".into(), 1, 48, Checksum::Codex32).err(),
+            Some(Error::BadThreshold { threshold: 1 }),
+        );
+    }
+
+    #[test]
+    fn version() {
+        let version = super::version();
+        let (semver, build) = version.split_once('+').unwrap();
+        assert_eq!(semver, env!("CARGO_PKG_VERSION"));
+        assert_eq!(semver.split('.').count(), 3);
+        for part in semver.split('.') {
+            assert!(part.parse::<u32>().is_ok());
+        }
+        assert_eq!(build, format!("storage.{}", STORAGE_FORMAT_VERSION));
+    }
+
+    #[test]
+    fn minimal_bech32() {
+        let session = SessionBuilder::new("ms", 2, 17, Checksum::Bech32)
+            .shares(1)
+            .type_str(0, 0, 0, "ccccc")
+            .type_str(0, 2, 6, "cc")
+            .type_str(0, 0, 5, "c")
+            .build();
+        assert_eq!(session.shares[0].header_str(), "CCCCCC");
+        assert_eq!(
+            snapshot(&session),
+            "\
+share 0:
+MS1CCCCCC
+  +69EXR9
+  =ZAP7MACC
+    +86Y602
+    =XYL8HJ5J
+      +YKFGUW
+      =M376GU3C
+        +UJGMTF
+        =ZGQ863GS
+          +Q863G3
+          =QQQQQP
+",
+        );
+    }
+
+    #[test]
+    fn test_cell_from_name() {
+        assert_eq!(cell_from_name("inp_0_0_0"), Ok([0, 0, 0]));
+        assert_eq!(cell_from_name("inp_0_10_0"), Ok([0, 10, 0]));
+        assert_eq!(cell_from_name("inp_100_10_10"), Ok([100, 10, 10]));
+        assert!(cell_from_name("inp_10_10_10_10").is_err());
+        assert!(cell_from_name("inp_10_10").is_err());
+        assert!(cell_from_name("inP_10_10_10").is_err());
+        assert!(cell_from_name("inp_10").is_err());
         assert!(cell_from_name("inp___").is_err());
     }
+
+    #[test]
+    fn shares_needed_for_recovery() {
+        let mut session = Session::new("ms".into(), 3, 48, Checksum::Codex32);
+        assert_eq!(session.shares_needed_for_recovery(), 3);
+        session.new_share().unwrap();
+        session.new_share().unwrap();
+        assert_eq!(session.shares_needed_for_recovery(), 3);
+        fill_share(&mut session, 0, SHARE_2NAMEA);
+        assert!(session.shares[0].is_complete());
+        assert!(!session.shares[1].is_complete());
+        assert_eq!(session.shares_needed_for_recovery(), 2);
+        assert_eq!(
+            session.shares[0].to_share_string().as_deref(),
+            Some(SHARE_2NAMEA)
+        );
+    }
+
+    #[test]
+    fn share_summary() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        session.auto_header = true;
+        session.new_share().unwrap();
+        session.new_share().unwrap();
+        fill_share(&mut session, 1, SHARE_2NAMEA);
+
+        let summary = session.share_summary(0).unwrap();
+        assert_eq!(summary.header(), "2____A");
+        assert_eq!(summary.completion, 2.0 / 45.0);
+        assert!(!summary.valid);
+        assert_eq!(summary.index, Some('A'));
+
+        let summary = session.share_summary(1).unwrap();
+        assert_eq!(summary.header(), "2NAMEA");
+        assert_eq!(summary.completion, 1.0);
+        assert!(summary.valid);
+        assert_eq!(summary.index, Some('A'));
+
+        // In create mode the checksum is recomputed to match
+        session.shares[1].set_data_char(6, 'Q').unwrap();
+        assert!(session.share_summary(1).unwrap().valid);
+    }
+
+    #[test]
+    fn auto_header() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        session.auto_header = true;
+        for _ in 0..3 {
+            session.new_share().unwrap();
+        }
+        let headers: Vec<_> = session.shares.iter().map(|s| s.header_str()).collect();
+        assert_eq!(headers, ["2____A", "2____C", "2____D"]);
+
+        let mut session = Session::new("ms".into(), 0, 48, Checksum::Codex32);
+        session.auto_header = true;
+        session.new_share().unwrap();
+        assert_eq!(session.shares[0].header_str(), "0____S");
+
+        session.auto_header = false;
+        session.new_share().unwrap();
+        assert_eq!(session.shares[1].header_str(), "______");
+
+        // It is off by default, and bech32 shares have no header to fill in
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        session.new_share().unwrap();
+        assert_eq!(session.shares[0].header_str(), "______");
+        let mut session = Session::new("ms".into(), 2, 17, Checksum::Bech32);
+        session.auto_header = true;
+        session.new_share().unwrap();
+        assert_eq!(session.shares[0].header_str(), "______");
+    }
+
+    #[test]
+    fn clone_for_verification() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        session.new_share().unwrap();
+        session.new_share().unwrap();
+        fill_share(&mut session, 0, SHARE_2NAMEA);
+
+        let mut verify = session.clone_for_verification();
+        for (orig, share) in session.shares.iter().zip(verify.shares.iter_mut()) {
+            assert_eq!(share.mode(), CreateMode::Verify);
+            assert_eq!(share.to_share_string(), orig.to_share_string());
+            share.recompute_all();
+        }
+        assert!(verify.shares[0].is_valid());
+        assert!(!verify.shares[1].is_valid());
+
+        // Once recomputed, the verification sheet is identical except for mode
+        verify.shares[0].set_mode(CreateMode::Create);
+        verify.shares[0].recompute_all();
+        assert_eq!(verify.shares[0], session.shares[0]);
+    }
+
+    #[test]
+    fn local_storage_checksum() {
+        for &(hrp, size, checksum) in &[
+            ("ms", 48, Checksum::Codex32),
+            ("bc", 43, Checksum::Bech32),
+            ("ms", 126, Checksum::Codex32Long),
+        ] {
+            let mut session = Session::new(hrp.into(), 2, size, checksum);
+            session.new_share().unwrap();
+            let s = session.local_storage_str();
+
+            let mut restored = Session::new("".into(), 0, 0, Checksum::Codex32);
+            assert!(restored.update_from_local_storage_str(&s).is_ok());
+            assert_eq!(restored.checksum, checksum);
+            assert_eq!(restored.size, size);
+            assert_eq!(restored.local_storage_str(), s);
+        }
+    }
+
+    #[test]
+    fn summary_json() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        session.auto_header = true;
+        session.new_share().unwrap();
+        session.new_share().unwrap();
+        fill_share(&mut session, 0, SHARE_2NAMEA);
+
+        let json = session.summary_json();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["checksum"], "Codex32");
+        assert_eq!(value["size"], 48);
+        assert_eq!(value["n_shares"], 2);
+        assert_eq!(value["shares"][0]["completion"], 1.0);
+        assert_eq!(value["shares"][0]["valid"], true);
+        assert_eq!(value["shares"][1]["complete"], false);
+        assert!(value["shares"][1]["completion"].as_f64().unwrap() < 0.2);
+
+        // No share data leaks into the summary
+        for share in &session.shares {
+            assert!(!json.contains(&share.header_str().replace('_', "")));
+        }
+        assert!(!json.contains("NAME"));
+        assert!(!json.contains(&SHARE_2NAMEA[9..]));
+    }
+
+    #[test]
+    fn set_size() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        session.auto_header = true;
+        session.new_share().unwrap();
+        fill_share(&mut session, 0, SHARE_2NAMEA);
+
+        assert_eq!(
+            session.set_size_inner(49),
+            Err(Error::OddLength { data_len: 33 })
+        );
+        assert!(session.set_size_inner(10).is_err());
+        assert_eq!(session.size(), 48);
+        assert!(session.shares[0].is_valid());
+
+        session.set_size_inner(60).unwrap();
+        assert_eq!(session.size(), 60);
+        assert_eq!(session.shares[0].header_str(), "2NAMEA");
+        assert!(session.shares[0].to_share_string().is_none());
+        session.new_share_inner().unwrap();
+        assert_eq!(session.share_summary(1).unwrap().header(), "2____C");
+    }
+
+    #[test]
+    fn flash_case() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        session.auto_header = false;
+        session.new_share().unwrap();
+
+        let actions = session.apply_input("inp_0_0_1", "n").unwrap();
+        assert_eq!(actions[0].as_tuple(), ("flash_set", "inp_0_0_1", Some('N')));
+
+        session.flash_case = false;
+        let actions = session.apply_input("inp_0_0_2", "a").unwrap();
+        assert_eq!(actions[0].as_tuple(), ("set", "inp_0_0_2", Some('A')));
+        assert!(actions.iter().all(|action| action.ty() != "flash_set"));
+        assert_eq!(session.shares[0].header_str(), "_NA___");
+    }
+
+    #[test]
+    fn share_checksum() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        session.new_share().unwrap();
+        assert_eq!(session.share_checksum(0).unwrap(), Checksum::Codex32);
+
+        let mut session = Session::new("ms".into(), 2, 126, Checksum::Codex32Long);
+        session.new_share().unwrap();
+        assert_eq!(session.share_checksum(0).unwrap(), Checksum::Codex32Long);
+    }
+
+    #[test]
+    fn share_mode() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        session.new_share().unwrap();
+        session.new_share().unwrap();
+        fill_share(&mut session, 0, SHARE_2NAMEA);
+        session.set_share_mode(1, CreateMode::Verify).unwrap();
+        assert_eq!(session.share_mode(0).unwrap(), CreateMode::Create);
+        assert_eq!(session.share_mode(1).unwrap(), CreateMode::Verify);
+        assert_eq!(session.share_summary(1).unwrap().mode, CreateMode::Verify);
+
+        let mut restored = Session::new("".into(), 0, 0, Checksum::Codex32);
+        assert!(restored
+            .update_from_local_storage_str(&session.local_storage_str())
+            .is_ok());
+        assert_eq!(restored.share_mode(0).unwrap(), CreateMode::Create);
+        assert_eq!(restored.share_mode(1).unwrap(), CreateMode::Verify);
+        assert_eq!(restored, session);
+
+        // Sessions saved without modes (or revisions) load in create mode
+        let mut s = String::new();
+        session.shares[0].cells_into_str(&mut s);
+        let (cells, _) = s.split_once(':').unwrap();
+        let old = format!("48_1_2_2_ms_{}", cells);
+        let mut restored = Session::new("".into(), 0, 0, Checksum::Codex32);
+        assert!(restored.update_from_local_storage_str(&old).is_ok());
+        assert_eq!(restored.share_mode(0).unwrap(), CreateMode::Create);
+        assert_eq!(restored.shares[0], session.shares[0]);
+    }
+
+    #[test]
+    fn load_legacy_local_storage() {
+        // Saved by the original version of the site, before share modes existed:
+        // a codex32 session with one partly-filled share and one empty share
+        let s = format!(
+            "48_1_2_2_ms_2NAMEA320ZYXW33XW87RR3YLJGMZM47RJF7XM5X{}2MDDJ44A38E563WNWQUTM2NL{}\
+             J40D7EEYFPAS6PM034ZNHK{}QWRSEZZYV8FEF0LKJ245{}4TWV2TTQS8J7CREYE7{}\
+             KHD8ZSS67VSHWJWN{}DSUAUQQH6LAQN7{}SECRETSHARE32_{}33XW87RR3YLJG{}SECRETSHARE32",
+            " ".repeat(2),
+            " ".repeat(4),
+            " ".repeat(6),
+            " ".repeat(8),
+            " ".repeat(10),
+            " ".repeat(12),
+            " ".repeat(279),
+            " ".repeat(13),
+            " ".repeat(448),
+        );
+        let mut restored = Session::new("".into(), 0, 0, Checksum::Bech32);
+        restored.update_from_local_storage_str(&s).unwrap();
+        assert_eq!(restored.checksum, Checksum::Codex32);
+        assert_eq!(restored.hrp, "ms");
+        assert_eq!(restored.threshold, 2);
+        assert_eq!(restored.size, 48);
+        assert_eq!(restored.shares.len(), 2);
+        assert_eq!(restored.shares[0].mode(), CreateMode::Create);
+        assert_eq!(restored.shares[0].header_str(), "2NAMEA");
+        assert!(restored.shares[0].check_consistency().is_empty());
+        assert_eq!(restored.shares[1].header_str(), "______");
+
+        // It is saved again in the same numbering
+        assert!(restored.local_storage_str().starts_with("48_1_2_2_ms_0:"));
+    }
+
+    #[test]
+    fn cell_from_name_fuzz() {
+        // Small xorshift PRNG so the test is deterministic without extra dependencies
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut rand = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let pieces = [
+            "inp",
+            "_",
+            "0",
+            "1",
+            "13",
+            "34",
+            "35",
+            "-1",
+            "+1",
+            "",
+            "x",
+            "é",
+            " ",
+            "18446744073709551615",
+            "18446744073709551616",
+            "99999999999999999999999",
+        ];
+
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        session.new_share().unwrap();
+        session.new_share().unwrap();
+        for i in 0..20_000 {
+            let id = if i % 2 == 0 {
+                // Well-formed ids with arbitrary numbers
+                let mut nums = [0usize; 3];
+                for n in &mut nums {
+                    *n = match rand() % 4 {
+                        0 => rand() as usize,
+                        1 => usize::MAX - (rand() % 4) as usize,
+                        _ => (rand() % 40) as usize,
+                    };
+                }
+                format!("inp_{}_{}_{}", nums[0], nums[1], nums[2])
+            } else {
+                // Arbitrary concatenations of id-like fragments
+                let len = rand() % 10;
+                (0..len)
+                    .map(|_| pieces[(rand() % pieces.len() as u64) as usize])
+                    .collect()
+            };
+            let val = pieces[(rand() % pieces.len() as u64) as usize];
+
+            // Errors are fine, panics are not
+            let _ = cell_from_name(&id);
+            let _ = session.apply_input(&id, val);
+        }
+    }
+
+    #[test]
+    fn export_all_shares() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        for _ in 0..3 {
+            session.new_share().unwrap();
+        }
+        fill_share(&mut session, 0, SHARE_2NAMEA);
+        fill_share(&mut session, 2, SHARE_2NAMEC);
+        assert_eq!(
+            session.share_strings(),
+            Err(Error::IncompleteShare { idx: 1 })
+        );
+
+        session.shares.remove(1);
+        assert_eq!(
+            session.share_strings().unwrap(),
+            [SHARE_2NAMEA, SHARE_2NAMEC],
+        );
+    }
+
+    #[test]
+    fn import_all_shares() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        let batch = [SHARE_2NAMEA.to_string(), SHARE_2NAMEC.to_string()];
+        assert!(session.import_all_shares_inner(&batch).is_ok());
+        assert_eq!(session.share_strings().unwrap(), batch);
+
+        // A batch with any bad share is rejected as a whole
+        let before = session.clone();
+        let bad_hrp = format!("MT{}", &SHARE_2NAMEC[2..]);
+        let bad_checksum = format!("{}Q", &SHARE_2NAMEC[..47]);
+        let bad_size = SHARE_2NAMEC[..47].to_string();
+        for bad in [bad_hrp, bad_checksum, bad_size] {
+            let batch = [SHARE_2NAMEA.to_string(), bad];
+            assert!(session.import_all_shares_inner(&batch).is_err());
+            assert_eq!(session, before);
+        }
+        assert_eq!(
+            session.parse_share(&format!("MT{}", &SHARE_2NAMEC[2..]), 2),
+            Err(Error::HrpMismatch {
+                expected: "ms".into(),
+                actual: "MT".into(),
+            }),
+        );
+    }
+
+    #[test]
+    fn import_share_checksum() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        assert_eq!(session.import_share(SHARE_2NAMEA).ok(), Some(0));
+
+        // The last character of the checksum is wrong
+        let bad = format!("{}Q", &SHARE_2NAMEC[..47]);
+        assert_eq!(
+            session.parse_share(&bad, 1).err(),
+            Some(Error::ChecksumMismatch {
+                positions: vec![47]
+            }),
+        );
+        // Lowercase shares are checked the same way
+        let bad = format!("{}x{}", &SHARE_2NAMEC[..40], &SHARE_2NAMEC[41..]).to_lowercase();
+        assert_eq!(
+            session.parse_share(&bad, 1).err(),
+            Some(Error::ChecksumMismatch {
+                positions: vec![40]
+            }),
+        );
+        assert!(session.parse_share(&SHARE_2NAMEC.to_lowercase(), 1).is_ok());
+    }
+
+    #[test]
+    fn import_grouped_share() {
+        let grouped = "ms1 2nam ea32 0zyx wvut srqp nmlk jhgf edca xrpp 870h kkqr m";
+        let session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        assert_eq!(
+            session.parse_share(grouped, 0),
+            session.parse_share(SHARE_2NAMEA, 0)
+        );
+        let dashed = "MS1-2NAM-EA32-0ZYX-WVUT\nSRQP-NMLK-JHGF-EDCA\nXRPP-870H-KKQR-M";
+        assert_eq!(
+            Session::from_share_string_inner(dashed, Checksum::Codex32),
+            Session::from_share_string_inner(SHARE_2NAMEA, Checksum::Codex32)
+        );
+        // Other characters are still rejected
+        assert!(session
+            .parse_share(
+                "ms1 2nam.ea32 0zyx wvut srqp nmlk jhgf edca xrpp 870h kkqr m",
+                0
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn import_share_len() {
+        let session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        let short = &SHARE_2NAMEA[..46];
+        assert_eq!(
+            session.parse_share(short, 0).err(),
+            Some(Error::BadShareDataLen {
+                len: 43,
+                expected: 45
+            }),
+        );
+        assert_eq!(
+            Error::BadShareDataLen {
+                len: 43,
+                expected: 45
+            }
+            .to_string(),
+            "Share data has 43 characters but this share size needs 45"
+        );
+
+        // A session too short for its own HRP
+        let session = Session::new("ms".into(), 2, 2, Checksum::Codex32);
+        assert_eq!(
+            session.parse_share(SHARE_2NAMEA, 0).err(),
+            Some(Error::BadShareDataLen {
+                len: 45,
+                expected: 0
+            }),
+        );
+    }
+
+    #[test]
+    fn replace_share() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        for _ in 0..3 {
+            session.new_share().unwrap();
+        }
+        fill_share(&mut session, 1, SHARE_2NAMEA);
+        let ids: Vec<_> = session.shares.iter().map(|s| s.focus_order()).collect();
+
+        session.replace_share_inner(1, SHARE_2NAMEC).unwrap();
+        assert_eq!(session.shares.len(), 3);
+        assert_eq!(
+            session.shares[1].to_share_string().as_deref(),
+            Some(SHARE_2NAMEC)
+        );
+        // The replaced share is in verify mode, so has more input cells
+        assert_eq!(session.shares[0].focus_order(), ids[0]);
+        assert_eq!(session.shares[2].focus_order(), ids[2]);
+        let replaced = session.shares[1].focus_order();
+        assert!(replaced.len() > ids[1].len());
+        assert!(replaced.iter().all(|id| id.starts_with("inp_1_")));
+
+        // A bad share leaves the slot alone
+        assert!(session.replace_share_inner(1, "MS12NAMEA").is_err());
+        assert_eq!(
+            session.shares[1].to_share_string().as_deref(),
+            Some(SHARE_2NAMEC)
+        );
+        assert_eq!(
+            session.replace_share_inner(3, SHARE_2NAMEA),
+            Err(Error::InvalidShare {
+                idx: 3,
+                n_shares: 3
+            })
+        );
+    }
+
+    #[test]
+    fn from_share_string() {
+        let session = Session::from_share_string_inner(SHARE_2NAMEA, Checksum::Codex32).unwrap();
+        assert_eq!(session.hrp, "ms");
+        assert_eq!(session.size, SHARE_2NAMEA.len());
+        assert_eq!(session.threshold, 2);
+        assert_eq!(session.n_shares(), 1);
+        assert_eq!(session.shares[0].mode(), CreateMode::Verify);
+        assert_eq!(session.share_strings().unwrap(), [SHARE_2NAMEA]);
+
+        assert_eq!(
+            Session::from_share_string_inner("MS2NAMEA", Checksum::Codex32).err(),
+            Some(Error::MissingSeparator),
+        );
+    }
+
+    #[test]
+    fn common_identifier() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        assert_eq!(session.common_identifier_inner(), Ok(None));
+
+        session.new_share().unwrap();
+        session.new_share().unwrap();
+        fill_share(&mut session, 0, SHARE_2NAMEA);
+        // Incomplete shares are ignored
+        assert_eq!(session.common_identifier_inner(), Ok(Some("NAME".into())));
+        fill_share(&mut session, 1, SHARE_2NAMEC);
+        assert_eq!(session.common_identifier_inner(), Ok(Some("NAME".into())));
+
+        session.new_share().unwrap();
+        fill_share(
+            &mut session,
+            2,
+            "MS12CASHAQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQ",
+        );
+        assert_eq!(
+            session.common_identifier_inner(),
+            Err(Error::IdentifierMismatch {
+                idx: 2,
+                expected: "NAME".into(),
+                actual: "CASH".into(),
+            }),
+        );
+    }
+
+    #[test]
+    fn infer_threshold() {
+        let mut session = Session::new("ms".into(), 3, 48, Checksum::Codex32);
+        assert_eq!(session.infer_threshold_inner(), Ok(None));
+        session.new_share().unwrap();
+        session.new_share().unwrap();
+        // Incomplete shares don't count, even with the header filled in
+        assert_eq!(session.infer_threshold_inner(), Ok(None));
+
+        fill_share(&mut session, 0, SHARE_2NAMEA);
+        assert_eq!(session.infer_threshold_inner(), Ok(Some(2)));
+        fill_share(&mut session, 1, SHARE_2NAMEC);
+        assert_eq!(session.infer_threshold_inner(), Ok(Some(2)));
+
+        session.new_share().unwrap();
+        fill_share(
+            &mut session,
+            2,
+            "MS13NAMEDQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQ",
+        );
+        assert_eq!(
+            session.infer_threshold_inner(),
+            Err(Error::ThresholdMismatch {
+                idx: 2,
+                expected: 2,
+                actual: 3,
+            }),
+        );
+    }
+
+    #[test]
+    fn recover_secret() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        session.new_share().unwrap();
+        fill_share(&mut session, 0, SHARE_2NAMEA);
+        assert_eq!(
+            session.recover_secret_inner(),
+            Err(Error::NotEnoughShares { needed: 2, have: 1 }),
+        );
+
+        session.new_share().unwrap();
+        fill_share(&mut session, 1, SHARE_2NAMEC);
+        let secret = "MS12NAMES6XQGUZTTXKEQNJSJZV4JV3NZ5K3KWGSPHUH6EVW";
+        assert_eq!(session.recover_secret_inner().unwrap(), secret);
+
+        let matrix = session.recovery_matrix_inner().unwrap();
+        assert_eq!(matrix.len(), 2);
+        for row in &matrix {
+            assert_eq!(row.len(), 45);
+        }
+        // Adding up the columns reproduces the secret
+        for (pos, ch) in secret[3..].chars().enumerate() {
+            let sum = matrix
+                .iter()
+                .fold(Fe::zero(), |acc, row| acc + Fe::try_from(row[pos]).unwrap());
+            assert_eq!(char::from(sum), ch);
+        }
+    }
+
+    #[test]
+    fn share_limit() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        assert_eq!(session.share_limit, 32);
+        session.auto_header = false;
+        session.share_limit = 3;
+        for idx in 0..3 {
+            assert_eq!(session.new_share_inner(), Ok(idx));
+        }
+        assert_eq!(
+            session.new_share_inner(),
+            Err(Error::TooManyShares { limit: 3 })
+        );
+        assert_eq!(session.shares.len(), 3);
+    }
+
+    #[test]
+    fn error_capacity() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        assert_eq!(session.error_detection_capacity(), 8);
+        assert_eq!(session.error_correction_capacity(), 4);
+        session.set_checksum(Checksum::Bech32);
+        assert_eq!(session.error_detection_capacity(), 4);
+        assert_eq!(session.error_correction_capacity(), 2);
+    }
+
+    #[test]
+    fn with_capacity() {
+        let session = Session::with_capacity("ms".into(), 2, 48, Checksum::Codex32, 5);
+        assert!(session.is_empty());
+        assert!(session.shares.capacity() >= 5);
+        assert_eq!(session, Session::new("ms".into(), 2, 48, Checksum::Codex32));
+
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        for _ in 0..3 {
+            session.new_share().unwrap();
+        }
+        fill_share(&mut session, 0, SHARE_2NAMEA);
+        fill_share(&mut session, 2, SHARE_2NAMEC);
+        let restored = local_storage_roundtrip(&session);
+        assert!(restored.shares.capacity() >= 3);
+        assert_eq!(restored.n_shares(), 3);
+        assert_eq!(
+            restored.share_strings(),
+            Err(Error::IncompleteShare { idx: 1 })
+        );
+        assert_eq!(
+            restored.shares[2].to_share_string().as_deref(),
+            Some(SHARE_2NAMEC)
+        );
+    }
+
+    #[test]
+    fn shares_info() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        session.auto_header = true;
+        assert!(session.shares_info_inner().is_empty());
+        session.import_share(SHARE_2NAMEA).unwrap();
+        session.new_share().unwrap();
+        fill_share(&mut session, 1, SHARE_2NAMEC);
+
+        let info = session.shares_info_inner();
+        assert_eq!(info.len(), session.n_shares());
+        assert_eq!(info[0].idx, 0);
+        assert_eq!(info[0].header(), "2NAMEA");
+        assert!(info[0].complete && info[0].valid);
+        assert_eq!(info[1].idx, 1);
+        assert_eq!(info[1].header(), "2NAMEC");
+        assert!(info[1].complete && info[1].valid);
+
+        session.new_share().unwrap();
+        let info = session.shares_info_inner();
+        assert_eq!(info.len(), session.n_shares());
+        assert_eq!(info[2].header(), "2____D");
+        assert!(!info[2].complete && !info[2].valid);
+    }
+
+    #[test]
+    fn compare_shares() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        session.auto_header = true;
+        session
+            .import_all_shares_inner(&[SHARE_2NAMEA.into(), SHARE_2NAMEA.into()])
+            .unwrap();
+        assert_eq!(session.compare_shares_inner(0, 1), Ok(vec![]));
+
+        // Change one character of the identifier
+        session.shares[1].set_data_char(2, 'X').unwrap();
+        assert_eq!(
+            session.compare_shares_inner(0, 1),
+            Ok(vec![(2, Some('A'), Some('X'))]),
+        );
+        assert_eq!(
+            session.compare_shares_inner(1, 0),
+            Ok(vec![(2, Some('X'), Some('A'))]),
+        );
+        // Positions are counted through the share data, not by worksheet cell
+        session.shares[1].set_data_char(2, 'A').unwrap();
+        session.shares[1].set_data_char(44, 'Q').unwrap();
+        assert_eq!(
+            session.compare_shares_inner(0, 1),
+            Ok(vec![(44, Some('M'), Some('Q'))]),
+        );
+
+        session.new_share().unwrap();
+        // The new share only has its threshold and index filled in, and only the
+        // threshold matches
+        let diffs = session.compare_shares_inner(0, 2).unwrap();
+        assert_eq!(diffs.len(), 44);
+        assert_eq!(diffs[0], (1, Some('N'), None));
+        assert_eq!(diffs[4], (5, Some('A'), Some('C')));
+        assert!(diffs.iter().all(|(_, a, _)| a.is_some()));
+
+        assert_eq!(
+            session.compare_shares_inner(0, 3),
+            Err(Error::InvalidShare {
+                idx: 3,
+                n_shares: 3
+            }),
+        );
+    }
+
+    #[test]
+    fn can_recover() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        session.new_share().unwrap();
+        fill_share(&mut session, 0, SHARE_2NAMEA);
+        // An incomplete share doesn't count
+        session.new_share().unwrap();
+        assert_eq!(
+            session.can_recover_inner(),
+            Err(Error::NotEnoughShares { needed: 2, have: 1 }),
+        );
+
+        fill_share(&mut session, 1, SHARE_2NAMEC);
+        assert_eq!(session.can_recover_inner(), Ok(()));
+
+        let mut dup = session.clone();
+        dup.import_all_shares_inner(&[SHARE_2NAMEA.into()]).unwrap();
+        assert_eq!(
+            dup.can_recover_inner(),
+            Err(Error::DuplicateShareIndex { index: 'A' }),
+        );
+
+        let secret = session.recover_secret_inner().unwrap();
+        let mut with_secret = session.clone();
+        with_secret
+            .import_all_shares_inner(std::slice::from_ref(&secret))
+            .unwrap();
+        assert_eq!(
+            with_secret.can_recover_inner(),
+            Err(Error::SecretShareProvided),
+        );
+
+        // With threshold 0 the secret is the only share needed
+        let mut unshared = Session::new("ms".into(), 0, 48, Checksum::Codex32);
+        assert_eq!(
+            unshared.can_recover_inner(),
+            Err(Error::NotEnoughShares { needed: 1, have: 0 }),
+        );
+        unshared.import_all_shares_inner(&[secret]).unwrap();
+        assert_eq!(unshared.can_recover_inner(), Ok(()));
+    }
+
+    #[test]
+    fn unshared_secret() {
+        let secret = "MS10TESTSXXXXXXXXXXXXXXXXXXXXXXXXXX4NZVCA9CMCZLW";
+        let mut session = Session::new("ms".into(), 0, 48, Checksum::Codex32);
+        session.auto_header = true;
+        assert_eq!(session.shares_needed_for_recovery(), 1);
+        assert_eq!(
+            session.recover_secret_inner(),
+            Err(Error::NotEnoughShares { needed: 1, have: 0 }),
+        );
+
+        session.new_share().unwrap();
+        assert_eq!(session.new_share_inner(), Err(Error::UnsharedSecret));
+        fill_share(&mut session, 0, secret);
+        assert_eq!(session.shares_needed_for_recovery(), 0);
+        assert_eq!(session.recover_secret_inner().as_deref(), Ok(secret));
+    }
+
+    #[test]
+    fn is_dirty() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        assert!(!session.is_dirty());
+        session.new_share().unwrap();
+        assert!(session.is_dirty());
+        let s = session.local_storage_str();
+        assert!(!session.is_dirty());
+
+        // Failed edits don't count
+        assert!(session.apply_input("inp_5_0_0", "2").is_err());
+        assert!(!session.is_dirty());
+        session.apply_input("inp_0_0_2", "N").unwrap();
+        assert!(session.is_dirty());
+        session.local_storage_str();
+        assert!(!session.is_dirty());
+
+        let batch = [("inp_0_0_3".to_string(), "A".to_string())];
+        session.apply_input_batch(&batch).unwrap();
+        assert!(session.is_dirty());
+        session.local_storage_str();
+        session.set_share_mode(0, CreateMode::Verify).unwrap();
+        assert!(session.is_dirty());
+        session.local_storage_str();
+        session.remove_share_inner(0).unwrap();
+        assert!(session.is_dirty());
+
+        // A freshly loaded session matches its storage
+        session.update_from_local_storage_str(&s).unwrap();
+        assert!(!session.is_dirty());
+
+        // Settings changed from JS count as changes
+        let saved = session.clone();
+        session.set_threshold(3);
+        assert!(session.is_dirty());
+        session.local_storage_str();
+        session.set_numeric(true);
+        assert!(session.is_dirty());
+        session.local_storage_str();
+        session.set_share_limit(4);
+        assert!(session.is_dirty());
+
+        // ...but whether a session has been saved doesn't affect equality
+        let unsaved = saved.clone();
+        unsaved.dirty.set(true);
+        assert_eq!(unsaved, saved);
+    }
+
+    #[test]
+    fn looks_like_share_in_hrp() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        assert!(!session.looks_like_share_in_hrp());
+        session.set_hrp("MS".into());
+        assert!(!session.looks_like_share_in_hrp());
+
+        session.set_hrp("ms1qqqqqqqq".into());
+        assert!(session.looks_like_share_in_hrp());
+        session.set_hrp(SHARE_2NAMEA.into());
+        assert!(session.looks_like_share_in_hrp());
+        // Too long to be a real HRP, even without a separator
+        session.set_hrp("qpzry9x8gf2tvdw0s3jn54khce6mua7l".into());
+        assert!(session.looks_like_share_in_hrp());
+    }
+
+    #[test]
+    fn remove_share() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        session.new_share().unwrap();
+        session.new_share().unwrap();
+        session.new_share().unwrap();
+        fill_share(&mut session, 2, SHARE_2NAMEC);
+        assert_eq!(
+            session.remove_share_inner(3),
+            Err(Error::InvalidShare {
+                idx: 3,
+                n_shares: 3
+            }),
+        );
+
+        session.remove_share_inner(1).unwrap();
+        assert_eq!(session.n_shares(), 2);
+        // Later shares move down but keep their DOM ids
+        assert_eq!(
+            session.shares[1].to_share_string().as_deref(),
+            Some(SHARE_2NAMEC)
+        );
+        assert_eq!(session.get_idx_of("inp_2_0_1").ok(), Some(1));
+        let actions = session.apply_input("inp_2_0_0", "3").unwrap();
+        assert!(actions.iter().all(|a| a.as_tuple().1.starts_with("inp_2_")));
+        assert_eq!(session.shares[1].threshold(), Some(3));
+        assert_eq!(session.shares[0].threshold(), None);
+
+        // The removed share's ids are not reused
+        assert_eq!(session.new_share_inner(), Ok(2));
+        assert_eq!(session.shares[2].idx(), 3);
+        session.apply_input("inp_3_0_0", "2").unwrap();
+        assert_eq!(session.shares[2].threshold(), Some(2));
+    }
+
+    #[test]
+    fn removed_share_events() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        session.new_share().unwrap();
+        session.new_share().unwrap();
+        session.new_share().unwrap();
+        fill_share(&mut session, 2, SHARE_2NAMEC);
+        let moved = session.shares[2].clone();
+
+        session.remove_share_inner(1).unwrap();
+        // A stale event for the removed share is distinguishable from a bad index,
+        // and does not touch the share which moved into its position
+        assert_eq!(
+            session.apply_input("inp_1_0_1", "N").err(),
+            Some(Error::RemovedShare { idx: 1 }),
+        );
+        assert_eq!(
+            session.apply_input("inp_3_0_1", "N").err(),
+            Some(Error::InvalidShare {
+                idx: 3,
+                n_shares: 2
+            }),
+        );
+        let batch = [
+            ("inp_2_0_1".to_string(), "N".to_string()),
+            ("inp_1_0_1".to_string(), "N".to_string()),
+        ];
+        assert_eq!(
+            session.apply_input_batch(&batch).err(),
+            Some(Error::RemovedShare { idx: 1 }),
+        );
+        assert_eq!(session.shares[1], moved);
+        session.apply_input("inp_2_0_1", "N").unwrap();
+
+        // Removing the last share also retires its id
+        session.remove_share_inner(1).unwrap();
+        assert_eq!(
+            session.apply_input("inp_2_0_1", "N").err(),
+            Some(Error::RemovedShare { idx: 2 }),
+        );
+
+        session.clear_all();
+        assert_eq!(
+            session.apply_input("inp_0_0_1", "N").err(),
+            Some(Error::RemovedShare { idx: 0 }),
+        );
+    }
+
+    #[test]
+    fn clear_all() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        session.auto_header = true;
+        assert!(session.is_empty());
+        session.record_inputs(true);
+        session.new_share().unwrap();
+        session.new_share().unwrap();
+        fill_share(&mut session, 0, SHARE_2NAMEA);
+        session.apply_input("inp_1_0_1", "N").unwrap();
+        assert!(!session.is_empty());
+
+        session.clear_all();
+        assert!(session.is_empty());
+        assert_eq!(session.n_shares(), 0);
+        assert!(session.input_log.is_empty());
+        // The session is still usable
+        assert_eq!(session.new_share().unwrap(), 0);
+        assert_eq!(session.shares[0].header_str(), "2____A");
+    }
+
+    /// Round-trips a session through local storage
+    fn local_storage_roundtrip(session: &Session) -> Session {
+        let s = session.local_storage_str();
+        let mut restored = Session::new("".into(), 0, 0, Checksum::Codex32);
+        if let Err(e) = restored.update_from_local_storage_str(&s) {
+            panic!("failed to restore {}: {:?}", s, e);
+        }
+        restored
+    }
+
+    #[test]
+    fn local_storage_roundtrip_matrix() {
+        // Each checksum with a size, and the number local storage uses for it
+        let shapes = [
+            (Checksum::Codex32, 48, 1),
+            (Checksum::Codex32, 92, 1),
+            (Checksum::Bech32, 17, 0),
+            (Checksum::Codex32Long, 126, 2),
+        ];
+        for hrp in ["ms", "MS", "a", "a_b", "_", "x1y", "a:b", "__9_"] {
+            for threshold in [0, 2, 9] {
+                for &(checksum, size, storage_checksum) in &shapes {
+                    // Keep the data length even
+                    let size = size + hrp.len() % 2;
+                    for n_shares in 0..=3 {
+                        // Settings which are not persisted are left at their defaults,
+                        // so the restored session compares equal
+                        let mut session = Session::new(hrp.into(), threshold, size, checksum);
+                        for idx in 0..n_shares {
+                            session.new_share_inner().unwrap();
+                            // Partially fill in each share, in alternating modes
+                            if idx % 2 == 1 {
+                                session.set_share_mode(idx, CreateMode::Verify).unwrap();
+                            }
+                            for pos in 0..3 * idx {
+                                session.shares[idx].set_data_char(pos, 'X').unwrap();
+                            }
+                        }
+
+                        let context =
+                            format!("{} {} {:?} {} {}", hrp, threshold, checksum, size, n_shares);
+                        let prefix = format!("{}_{}_{}_", size, storage_checksum, threshold);
+                        assert!(
+                            session.local_storage_str().starts_with(&prefix),
+                            "{}",
+                            context
+                        );
+                        let restored = local_storage_roundtrip(&session);
+                        assert_eq!(restored, session, "{}", context);
+                        assert_eq!(
+                            restored.local_storage_str(),
+                            session.local_storage_str(),
+                            "{}",
+                            context
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn load_shares() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        session.auto_header = true;
+        session.new_share().unwrap();
+        session.new_share().unwrap();
+        fill_share(&mut session, 0, SHARE_2NAMEA);
+        let s = session.local_storage_str();
+
+        // Corrupt the second share
+        let mut iter = s.rsplitn(2, '_');
+        let second = iter.next().unwrap();
+        let (cells, metadata) = second.rsplit_once(':').unwrap();
+        let corrupted = format!("{}B:{}", &cells[..cells.len() - 1], metadata);
+        let s = format!("{}_{}", iter.next().unwrap(), corrupted);
+
+        let shares = &s["48_1_2_2_ms_".len()..];
+        let mut restored = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        match restored.load_shares(shares) {
+            Err(Error::CorruptShare { idx, data, reason }) => {
+                assert_eq!(idx, 1);
+                assert_eq!(data, corrupted);
+                assert_eq!(reason, Error::BadBech32Char { ch: 'B' }.to_string());
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        // An unknown mode prefix
+        let mut restored = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        let (_, first) = s.split('_').nth(5).unwrap().split_once(':').unwrap();
+        assert_eq!(
+            restored.load_share(&format!("7:{}", first)),
+            Err(Error::BadShareMode { mode: "7".into() }),
+        );
+    }
+
+    #[test]
+    fn apply_input_batch() {
+        let mut single = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        single.new_share().unwrap();
+        single.new_share().unwrap();
+        let mut batch = single.clone();
+
+        let changes: Vec<(String, String)> = [
+            ("inp_0_0_1", "N"),
+            ("inp_1_0_1", "N"),
+            ("inp_0_0_2", "A"),
+            ("inp_0_2_13", "C"),
+            ("inp_0_0_2", "M"),
+        ]
+        .iter()
+        .map(|&(id, val)| (id.into(), val.into()))
+        .collect();
+        for (id, val) in &changes {
+            single.apply_input(id, val).unwrap();
+        }
+        batch.apply_input_batch(&changes).unwrap();
+        for (a, b) in batch.shares.iter().zip(&single.shares) {
+            assert_eq!(a.dom_cells_json(false), b.dom_cells_json(false));
+        }
+
+        // A bad cell in a later share leaves every share unchanged
+        let before = batch.clone();
+        let changes: Vec<(String, String)> =
+            [("inp_0_0_3", "M"), ("inp_1_0_2", "A"), ("inp_1_99_0", "Q")]
+                .iter()
+                .map(|&(id, val)| (id.into(), val.into()))
+                .collect();
+        assert!(matches!(
+            batch.apply_input_batch(&changes),
+            Err(Error::InvalidRow { row: 99, .. }),
+        ));
+        assert_eq!(batch, before);
+    }
+
+    #[test]
+    fn replay() {
+        let mut session = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        session.auto_header = false;
+        session.new_share().unwrap();
+        session.new_share().unwrap();
+        session.record_inputs(true);
+        for (n, ch) in "2NAME".chars().enumerate() {
+            session
+                .apply_input(&format!("inp_0_0_{}", n), &ch.to_string())
+                .unwrap();
+        }
+        session.apply_input("inp_1_0_0", "x").unwrap();
+        session.apply_input("inp_1_0_0", "").unwrap();
+        session.apply_input("inp_1_2_13", "q").unwrap();
+        session.record_inputs(false);
+        session.apply_input("inp_1_2_14", "q").unwrap();
+        assert_eq!(session.input_log.len(), 8);
+
+        let log = serde_json::to_string(&session.input_log).unwrap();
+        let mut replayed = Session::new("ms".into(), 2, 48, Checksum::Codex32);
+        replayed.auto_header = false;
+        replayed.new_share().unwrap();
+        replayed.new_share().unwrap();
+        replayed.replay_inner(&log).unwrap();
+        replayed.apply_input("inp_1_2_14", "q").unwrap();
+        assert_eq!(replayed.shares, session.shares);
+    }
 }
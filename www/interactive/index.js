@@ -192,7 +192,7 @@ function createChecksumWorksheet(idx, cells) {
         const domInp = document.createElement("input");
         domInp.id = cell.dom_id;
         domInp.disabled = true; // only a couple cell types are editable
-        domInp.value = cell.val || '';
+        domInp.value = cell.text;
         switch (cell.ty) {
         case "symbol":
             console.assert(cell.val !== undefined);
@@ -300,20 +300,24 @@ async function processActions() {
         }
 
         const elem = document.getElementById(action.id);
+        const value = (g_session.numeric ? action.value_num : action.value) || '';
         switch(action.ty) {
         case "flash_error":
             elem.style.color = "red";
             break;
         case "flash_set":
-            elem.value = action.value || '';
+            elem.value = value;
             elem.style.color = "green";
             setTimeout(() => { elem.style.color = "black"; }, 500);
             break;
         case "set":
-            elem.value = action.value || '';
+            elem.value = value;
             elem.style.fontWeight = "bold";
             setTimeout(() => { elem.style.fontWeight = "normal"; }, 500);
             break;
+        case "focus":
+            elem.focus();
+            break;
         }
     }, 20);
 }